@@ -0,0 +1,72 @@
+use d08::{get_value, parse_tree, sum_metadata, Result, Tree};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const DEPTH: usize = 12;
+
+// Counts every allocation and reallocation made through it.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// A complete tree where every inner node has three children and three metadata entries.
+fn generate_tree(depth: usize, out: &mut Vec<usize>) {
+    if depth == 0 {
+        out.extend(&[0, 2, 4, 7]);
+    } else {
+        out.extend(&[3, 3]);
+        for _ in 0..3 {
+            generate_tree(depth - 1, out);
+        }
+        out.extend(&[1, 3, 3]);
+    }
+}
+
+fn measure(name: &str, solve: impl FnOnce() -> Result<(usize, usize)>) -> Result<(usize, usize)> {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    let answers = solve()?;
+    println!(
+        "{:>5}: {:?}, {} allocations",
+        name,
+        start.elapsed(),
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations
+    );
+    Ok(answers)
+}
+
+fn main() -> Result<()> {
+    let mut input = Vec::new();
+    generate_tree(DEPTH, &mut input);
+    println!("{} numbers", input.len());
+    let node = measure("Node", || {
+        let root = parse_tree(&mut input.iter().cloned())?;
+        Ok((sum_metadata(&root), get_value(&root)?))
+    })?;
+    let tree = measure("Tree", || {
+        let tree = Tree::parse(&mut input.iter().cloned())?;
+        Ok((tree.sum_metadata(), tree.get_value()))
+    })?;
+    assert!(node == tree, "Node and Tree disagree");
+    Ok(())
+}
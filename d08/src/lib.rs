@@ -0,0 +1,483 @@
+use std::error::Error;
+use std::fmt::Write;
+use std::io;
+use std::ops::Range;
+use std::result;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+pub struct Node {
+    children: Box<[Node]>,
+    metadata: Box<[usize]>,
+}
+
+// Arena representation: nodes are stored in preorder, so every child has a larger index than its
+// parent, and each node refers to its children and metadata by ranges into the shared Vecs.
+struct NodeData {
+    children: Range<usize>,
+    metadata: Range<usize>,
+}
+
+pub struct Tree {
+    nodes: Vec<NodeData>,
+    children: Vec<usize>,
+    metadata: Vec<usize>,
+}
+
+impl Tree {
+    pub fn parse<T: Iterator<Item = usize>>(it: &mut T) -> Result<Tree> {
+        let mut tree = Tree {
+            nodes: Vec::new(),
+            children: Vec::new(),
+            metadata: Vec::new(),
+        };
+        let mut tokens = Tokens { it, pos: 0 };
+        // Indices of the children parsed so far of every node on the stack, each node's after its
+        // parent's. Counts come straight from the input, so slots are only added as children are
+        // actually parsed.
+        let mut found = Vec::new();
+        let mut stack = vec![tree.push_node(tokens.header()?, 0)];
+        while let Some(top) = stack.last_mut() {
+            if top.children_left > 0 {
+                top.children_left -= 1;
+                let header = tokens.header()?;
+                found.push(tree.nodes.len());
+                stack.push(tree.push_node(header, found.len()));
+            } else {
+                let Pending {
+                    index,
+                    start,
+                    first_found,
+                    num_metadata,
+                    ..
+                } = stack.pop().unwrap();
+                let first = tree.children.len();
+                tree.children.extend(found.drain(first_found..));
+                tree.nodes[index].children = first..tree.children.len();
+                let first = tree.metadata.len();
+                for _ in 0..num_metadata {
+                    tree.metadata.push(tokens.next("metadata entry", start)?);
+                }
+                tree.nodes[index].metadata = first..tree.metadata.len();
+            }
+        }
+        Ok(tree)
+    }
+
+    // Adds a node whose children and metadata are filled in once they've been parsed.
+    fn push_node(
+        &mut self,
+        (start, num_children, num_metadata): (usize, usize, usize),
+        first_found: usize,
+    ) -> Pending {
+        let index = self.nodes.len();
+        self.nodes.push(NodeData {
+            children: 0..0,
+            metadata: 0..0,
+        });
+        Pending {
+            index,
+            start,
+            children_left: num_children,
+            first_found,
+            num_metadata,
+        }
+    }
+
+    pub fn children(&self, index: usize) -> &[usize] {
+        &self.children[self.nodes[index].children.clone()]
+    }
+
+    pub fn metadata(&self, index: usize) -> &[usize] {
+        &self.metadata[self.nodes[index].metadata.clone()]
+    }
+
+    pub fn sum_metadata(&self) -> usize {
+        self.metadata.iter().sum()
+    }
+
+    pub fn get_value(&self) -> usize {
+        self.values().first().cloned().unwrap_or(0)
+    }
+
+    // Values of every node, indexed like `nodes`.
+    fn values(&self) -> Vec<usize> {
+        let mut values = vec![0; self.nodes.len()];
+        for index in (0..self.nodes.len()).rev() {
+            let children = self.children(index);
+            values[index] = if children.is_empty() {
+                self.metadata(index).iter().sum()
+            } else {
+                self.metadata(index)
+                    .iter()
+                    .filter(|&&i| i != 0 && i <= children.len())
+                    .map(|&i| values[children[i - 1]])
+                    .sum()
+            };
+        }
+        values
+    }
+
+    pub fn find(&self, path: &[usize]) -> Result<usize> {
+        let mut index = 0;
+        for (depth, &i) in path.iter().enumerate() {
+            let children = self.children(index);
+            index = *children.get(i).ok_or_else(|| {
+                format!(
+                    "no child {} at path `{}` (node has {} children)",
+                    i,
+                    format_path(&path[..depth]),
+                    children.len()
+                )
+            })?;
+        }
+        Ok(index)
+    }
+
+    pub fn describe(&self, path: &[usize]) -> Result<String> {
+        let index = self.find(path)?;
+        let metadata = self.metadata(index);
+        Ok(format!(
+            "metadata: {:?}\nmetadata sum: {}\nvalue: {}\n",
+            metadata,
+            metadata.iter().sum::<usize>(),
+            self.values()[index]
+        ))
+    }
+
+    // Streams the tree as nested `{"metadata":[...],"value":N,"children":[...]}` objects.
+    pub fn write_json(&self, out: &mut impl io::Write) -> io::Result<()> {
+        self.write_json_node(0, &self.values(), out)
+    }
+
+    fn write_json_node(
+        &self,
+        index: usize,
+        values: &[usize],
+        out: &mut impl io::Write,
+    ) -> io::Result<()> {
+        write!(out, "{{\"metadata\":[")?;
+        for (i, metadata) in self.metadata(index).iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(out, "{}", metadata)?;
+        }
+        write!(out, "],\"value\":{},\"children\":[", values[index])?;
+        for (i, &child) in self.children(index).iter().enumerate() {
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            self.write_json_node(child, values, out)?;
+        }
+        write!(out, "]}}")
+    }
+
+    pub fn to_node(&self, index: usize) -> Node {
+        Node {
+            children: self
+                .children(index)
+                .iter()
+                .map(|&child| self.to_node(child))
+                .collect(),
+            metadata: self.metadata(index).into(),
+        }
+    }
+}
+
+// A node still being parsed: how many children are left to parse, where its children so far
+// start in the parser's list, and the metadata to read after them.
+struct Pending {
+    index: usize,
+    start: usize,
+    children_left: usize,
+    first_found: usize,
+    num_metadata: usize,
+}
+
+// Tracks how many numbers have been consumed so errors can point at the offending token.
+struct Tokens<'a, T> {
+    it: &'a mut T,
+    pos: usize,
+}
+
+impl<T: Iterator<Item = usize>> Tokens<'_, T> {
+    fn next(&mut self, expected: &str, node: usize) -> Result<usize> {
+        let token = self
+            .it
+            .next()
+            .ok_or_else(|| format!("expected {} for node at token {}", expected, node))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    // Returns the token index the node starts at along with its child and metadata counts.
+    fn header(&mut self) -> Result<(usize, usize, usize)> {
+        let start = self.pos;
+        let num_children = self.next("child count", start)?;
+        let num_metadata = self.next("metadata count", start)?;
+        Ok((start, num_children, num_metadata))
+    }
+}
+
+pub fn parse_input(input: &str) -> Result<Vec<usize>> {
+    input
+        .split_whitespace()
+        .enumerate()
+        .map(|(i, token)| {
+            token
+                .parse()
+                .map_err(|e| format!("invalid number `{}` at token {}: {}", token, i, e).into())
+        })
+        .collect()
+}
+
+// Sums the metadata in a single pass without building the tree, keeping only the unfinished
+// (start, children left, metadata count) headers on an explicit stack.
+pub fn stream_metadata_sum(it: &mut impl Iterator<Item = usize>) -> Result<usize> {
+    let mut tokens = Tokens { it, pos: 0 };
+    let mut stack = vec![tokens.header()?];
+    let mut sum = 0;
+    while let Some(top) = stack.last_mut() {
+        if top.1 > 0 {
+            top.1 -= 1;
+            let header = tokens.header()?;
+            stack.push(header);
+        } else {
+            let (start, _, num_metadata) = stack.pop().unwrap();
+            for _ in 0..num_metadata {
+                sum += tokens.next("metadata entry", start)?;
+            }
+        }
+    }
+    Ok(sum)
+}
+
+pub fn parse_path(s: &str) -> Result<Vec<usize>> {
+    if s.is_empty() {
+        return Ok(Vec::new());
+    }
+    s.split('.')
+        .map(|i| {
+            i.parse()
+                .map_err(|_| format!("malformed node path `{}`", s).into())
+        })
+        .collect()
+}
+
+fn format_path(path: &[usize]) -> String {
+    path.iter()
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+pub fn parse_tree<T: Iterator<Item = usize>>(it: &mut T) -> Result<Node> {
+    Ok(Tree::parse(it)?.to_node(0))
+}
+
+pub fn sum_metadata(node: &Node) -> usize {
+    node.children.iter().map(sum_metadata).sum::<usize>() + node.metadata.iter().sum::<usize>()
+}
+
+pub fn get_value(node: &Node) -> Result<usize> {
+    if node.children.is_empty() {
+        Ok(node.metadata.iter().sum())
+    } else {
+        let mut sum = 0;
+        for i in node.metadata.iter().cloned() {
+            if i != 0 && i <= node.children.len() {
+                sum += get_value(&node.children[i - 1])?;
+            }
+        }
+        Ok(sum)
+    }
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Nodes are numbered in preorder so the ids are stable for a given input.
+fn write_dot_node(node: &Node, next_id: &mut usize, out: &mut String) -> Result<()> {
+    let id = *next_id;
+    *next_id += 1;
+    let metadata = escape_label(&format!("{:?}", node.metadata));
+    writeln!(
+        out,
+        "    n{} [label=\"{}\\nvalue = {}\"];",
+        id,
+        metadata,
+        get_value(node)?
+    )?;
+    for child in node.children.iter() {
+        writeln!(out, "    n{} -> n{};", id, *next_id)?;
+        write_dot_node(child, next_id, out)?;
+    }
+    Ok(())
+}
+
+pub fn to_dot(root: &Node) -> Result<String> {
+    let mut out = String::from("digraph license {\n");
+    write_dot_node(root, &mut 0, &mut out)?;
+    out.push_str("}\n");
+    Ok(out)
+}
+
+#[cfg(test)]
+const EXAMPLE: [usize; 16] = [2, 3, 0, 3, 10, 11, 12, 1, 1, 0, 1, 99, 2, 1, 1, 2];
+
+#[test]
+fn test_to_dot() -> Result<()> {
+    let root = parse_tree(&mut EXAMPLE.iter().cloned())?;
+    let dot = to_dot(&root)?;
+    assert!(dot.starts_with("digraph license {\n"));
+    assert!(dot.contains("    n0 [label=\"[1, 1, 2]\\nvalue = 66\"];\n"));
+    assert!(dot.contains("    n3 [label=\"[99]\\nvalue = 99\"];\n"));
+    let edges: Vec<&str> = dot.lines().filter(|l| l.contains("->")).collect();
+    assert_eq!(edges, ["    n0 -> n1;", "    n0 -> n2;", "    n2 -> n3;"]);
+    Ok(())
+}
+
+#[cfg(test)]
+fn generate_tree(depth: usize, out: &mut Vec<usize>) {
+    if depth == 0 {
+        out.extend(&[0, 2, 4, 7]);
+    } else {
+        out.extend(&[3, 3]);
+        for _ in 0..3 {
+            generate_tree(depth - 1, out);
+        }
+        out.extend(&[1, 3, 3]);
+    }
+}
+
+#[test]
+fn test_tree_matches_node() -> Result<()> {
+    let tree = Tree::parse(&mut EXAMPLE.iter().cloned())?;
+    assert_eq!(tree.sum_metadata(), 138);
+    assert_eq!(tree.get_value(), 66);
+
+    let mut input = Vec::new();
+    generate_tree(10, &mut input);
+    let root = parse_tree(&mut input.iter().cloned())?;
+    let tree = Tree::parse(&mut input.iter().cloned())?;
+    assert_eq!(
+        (sum_metadata(&root), get_value(&root)?),
+        (tree.sum_metadata(), tree.get_value())
+    );
+    Ok(())
+}
+
+#[test]
+fn test_describe() -> Result<()> {
+    let tree = Tree::parse(&mut EXAMPLE.iter().cloned())?;
+    assert_eq!(
+        tree.describe(&parse_path("")?)?,
+        "metadata: [1, 1, 2]\nmetadata sum: 4\nvalue: 66\n"
+    );
+    assert_eq!(
+        tree.describe(&parse_path("1")?)?,
+        "metadata: [2]\nmetadata sum: 2\nvalue: 0\n"
+    );
+    assert_eq!(
+        tree.describe(&parse_path("1.0")?)?,
+        "metadata: [99]\nmetadata sum: 99\nvalue: 99\n"
+    );
+    assert_eq!(
+        tree.describe(&parse_path("1.2")?).unwrap_err().to_string(),
+        "no child 2 at path `1` (node has 1 children)"
+    );
+    assert_eq!(
+        tree.describe(&parse_path("0.0")?).unwrap_err().to_string(),
+        "no child 0 at path `0` (node has 0 children)"
+    );
+    assert!(parse_path("1.x").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_stream_metadata_sum() -> Result<()> {
+    assert_eq!(stream_metadata_sum(&mut EXAMPLE.iter().cloned())?, 138);
+
+    let mut input = Vec::new();
+    generate_tree(10, &mut input);
+    let tree = Tree::parse(&mut input.iter().cloned())?;
+    assert_eq!(
+        stream_metadata_sum(&mut input.iter().cloned())?,
+        tree.sum_metadata()
+    );
+
+    assert!(stream_metadata_sum(&mut EXAMPLE[..15].iter().cloned()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_oversized_child_count() {
+    // A child count far beyond the input fails on the missing children rather than reserving room
+    // for them all.
+    let input = [usize::MAX, 1, 5];
+    let err = Tree::parse(&mut input.iter().cloned()).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "expected metadata count for node at token 2"
+    );
+    assert!(stream_metadata_sum(&mut input.iter().cloned()).is_err());
+}
+
+#[test]
+fn test_write_json() -> Result<()> {
+    let tree = Tree::parse(&mut EXAMPLE.iter().cloned())?;
+    let mut out = Vec::new();
+    tree.write_json(&mut out)?;
+    assert_eq!(
+        String::from_utf8(out)?,
+        concat!(
+            r#"{"metadata":[1,1,2],"value":66,"children":["#,
+            r#"{"metadata":[10,11,12],"value":33,"children":[]},"#,
+            r#"{"metadata":[2],"value":0,"children":["#,
+            r#"{"metadata":[99],"value":99,"children":[]}]}]}"#
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_parse_input() -> Result<()> {
+    let input = parse_input("2 3 0  3 10 11 12\n1 1 0 1 99 2\n\t1 1 2\n")?;
+    assert_eq!(input, EXAMPLE);
+    assert_eq!(
+        parse_input("2 3 x").unwrap_err().to_string(),
+        "invalid number `x` at token 2: invalid digit found in string"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_truncated_input() -> Result<()> {
+    let err = |input: &[usize]| {
+        Tree::parse(&mut input.iter().cloned())
+            .err()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        err(&EXAMPLE[..8]),
+        "expected metadata count for node at token 7"
+    );
+    assert_eq!(
+        err(&EXAMPLE[..7]),
+        "expected child count for node at token 7"
+    );
+    assert_eq!(
+        err(&EXAMPLE[..14]),
+        "expected metadata entry for node at token 0"
+    );
+    assert_eq!(
+        stream_metadata_sum(&mut EXAMPLE[..8].iter().cloned())
+            .unwrap_err()
+            .to_string(),
+        "expected metadata count for node at token 7"
+    );
+    Ok(())
+}
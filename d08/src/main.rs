@@ -1,76 +1,44 @@
-use std::error::Error;
-use std::io::{self, Read};
-use std::result;
+use d08::{parse_input, parse_path, stream_metadata_sum, to_dot, Result, Tree};
+use std::env;
+use std::io::{self, BufWriter, Read, Write};
 
-type Result<T> = result::Result<T, Box<Error>>;
-
-struct Node {
-    children: Box<[Node]>,
-    metadata: Box<[usize]>,
-}
-
-fn parse_tree<T: Iterator<Item = usize>>(it: &mut T) -> Result<Node> {
-    let num_children = it
-        .next()
-        .ok_or_else(|| Box::<Error>::from("malformed header"))?;
-    let num_metadata = it
-        .next()
-        .ok_or_else(|| Box::<Error>::from("malformed header"))?;
-    let mut children = Vec::new();
-    let mut metadata = Vec::new();
-    for _ in 0..num_children {
-        children.push(parse_tree(it)?);
-    }
-    for _ in 0..num_metadata {
-        metadata.push(
-            it.next()
-                .ok_or_else(|| Box::<Error>::from("missing metadata"))?,
-        );
+fn main() -> Result<()> {
+    let mut dot = false;
+    let mut json = false;
+    let mut node = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dot" => dot = true,
+            "--json" => json = true,
+            "--node" => node = Some(parse_path(&args.next().ok_or("missing node path")?)?),
+            _ => return Err(format!("unexpected argument {}", arg).into()),
+        }
     }
-    let children = children.into_boxed_slice();
-    let metadata = metadata.into_boxed_slice();
-    Ok(Node { children, metadata })
-}
 
-fn main() -> Result<()> {
     let mut input = String::new();
     io::stdin().read_to_string(&mut input)?;
-    let input = input
-        .trim()
-        .split(' ')
-        .map(str::parse)
-        .collect::<result::Result<Vec<usize>, _>>()?;
-    let root = parse_tree(&mut input.into_iter())?;
-
-    part1(&root)?;
-    part2(&root)?;
-    Ok(())
-}
-
-fn sum_metadata(node: &Node) -> usize {
-    node.children.iter().map(sum_metadata).sum::<usize>() + node.metadata.iter().sum::<usize>()
-}
-
-fn part1(root: &Node) -> Result<()> {
-    println!("{}", sum_metadata(root));
-    Ok(())
-}
-
-fn get_value(node: &Node) -> Result<usize> {
-    if node.children.is_empty() {
-        Ok(node.metadata.iter().sum())
-    } else {
-        let mut sum = 0;
-        for i in node.metadata.iter().cloned() {
-            if i != 0 && i <= node.children.len() {
-                sum += get_value(&node.children[i - 1])?;
-            }
-        }
-        Ok(sum)
+    let input = parse_input(&input)?;
+    if !dot && !json && node.is_none() {
+        println!("{}", stream_metadata_sum(&mut input.iter().cloned())?);
     }
-}
+    let tree = Tree::parse(&mut input.into_iter())?;
 
-fn part2(root: &Node) -> Result<()> {
-    println!("{}", get_value(root)?);
+    if let Some(path) = node {
+        print!("{}", tree.describe(&path)?);
+        return Ok(());
+    }
+    if json {
+        let stdout = io::stdout();
+        let mut out = BufWriter::new(stdout.lock());
+        tree.write_json(&mut out)?;
+        writeln!(out)?;
+        return Ok(());
+    }
+    if dot {
+        print!("{}", to_dot(&tree.to_node(0))?);
+        return Ok(());
+    }
+    println!("{}", tree.get_value());
     Ok(())
 }
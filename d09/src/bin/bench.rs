@@ -0,0 +1,26 @@
+use d09::{play_game_with_segment_size, Result};
+use std::time::Instant;
+
+const NUM_PLAYERS: usize = 429;
+const LAST_MARBLE: u64 = 7_000_000;
+
+fn time_game<const N: usize>() -> Result<()> {
+    let start = Instant::now();
+    let result = play_game_with_segment_size::<N>(NUM_PLAYERS, LAST_MARBLE)?;
+    let (_, score) = result.winner().ok_or("need players")?;
+    println!(
+        "segment size {:>4}: {:?} (high score {})",
+        N,
+        start.elapsed(),
+        score
+    );
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    time_game::<16>()?;
+    time_game::<64>()?;
+    time_game::<256>()?;
+    time_game::<1024>()?;
+    Ok(())
+}
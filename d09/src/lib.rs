@@ -0,0 +1,865 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::iter;
+use std::iter::Peekable;
+use std::result;
+use std::str::Chars;
+use std::str::FromStr;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+// 16 and 64 are close for a 7-million-marble game and larger sizes are slower; see
+// `cargo run --release --bin bench`.
+pub const DEFAULT_SEGMENT_SIZE: usize = 64;
+
+// The operations a game needs from its circle of marbles. `cw_by` and `ccw_by` move the current
+// marble that many places around the circle. `insert` places a marble at the current position,
+// so the old current marble ends up clockwise of it, and makes it current; `remove` takes the
+// current marble and makes the one clockwise of it current.
+trait Ring {
+    fn new() -> Self;
+    fn cw_by(&mut self, n: usize);
+    fn ccw_by(&mut self, n: usize);
+    fn get(&self) -> u64;
+    fn insert(&mut self, marble: u64);
+    fn remove(&mut self);
+}
+
+struct MarbleSegment<const N: usize> {
+    marbles: [u64; N],
+    len: usize,
+    prev_segment: usize,
+    next_segment: usize,
+}
+
+impl<const N: usize> MarbleSegment<N> {
+    fn new(prev_segment: usize, next_segment: usize) -> Self {
+        Self {
+            marbles: [0; N],
+            len: 1,
+            prev_segment,
+            next_segment,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, n: usize) -> u64 {
+        self.marbles[n]
+    }
+
+    fn marbles(&self) -> &[u64] {
+        &self.marbles[..self.len]
+    }
+
+    fn needs_split(&self) -> bool {
+        self.len == N
+    }
+
+    fn is_sparse(&self) -> bool {
+        self.len < N / 4
+    }
+
+    fn insert(&mut self, n: usize, marble: u64) {
+        assert!(self.len < N);
+        assert!(n <= self.len);
+        for i in (n..self.len).rev() {
+            self.marbles[i + 1] = self.marbles[i];
+        }
+        self.marbles[n] = marble;
+        self.len += 1;
+    }
+
+    fn remove(&mut self, n: usize) {
+        assert!(self.len > 0);
+        for i in n + 1..self.len {
+            self.marbles[i - 1] = self.marbles[i];
+        }
+        self.len -= 1;
+    }
+
+    fn append(&mut self, other: &Self) {
+        assert!(self.len + other.len <= N);
+        self.marbles[self.len..self.len + other.len].copy_from_slice(&other.marbles[..other.len]);
+        self.len += other.len;
+    }
+
+    // prev/next_segment encapsulation is dubious.
+    fn split_off(&mut self, prev_segment: usize, next_segment: usize) -> Self {
+        let split_at = self.len / 2;
+        let mut marbles = [0; N];
+        marbles[..self.len - split_at].copy_from_slice(&self.marbles[split_at..self.len]);
+        let len = self.len - split_at;
+        self.len = split_at;
+        Self {
+            marbles,
+            len,
+            prev_segment,
+            next_segment,
+        }
+    }
+}
+
+impl<const N: usize> Debug for MarbleSegment<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", &self.marbles[..self.len])
+    }
+}
+
+struct MarbleRing<const N: usize = DEFAULT_SEGMENT_SIZE> {
+    // This should be a circular linked list but that's surprisingly hard with Rust ownership.
+    segments: Vec<MarbleSegment<N>>,
+    // Segments unlinked from the ring, to be reused by later splits.
+    free_segments: Vec<usize>,
+    current_segment: usize,
+    segment_index: usize,
+    len: usize,
+}
+
+impl<const N: usize> Ring for MarbleRing<N> {
+    fn new() -> Self {
+        Self {
+            segments: vec![MarbleSegment::new(0, 0)],
+            free_segments: Vec::new(),
+            current_segment: 0,
+            segment_index: 0,
+            len: 0,
+        }
+    }
+
+    fn ccw_by(&mut self, mut n: usize) {
+        while n > self.segment_index {
+            n -= self.segment_index + 1;
+            loop {
+                self.current_segment = self.segments[self.current_segment].prev_segment;
+                if self.segments[self.current_segment].len() > 0 {
+                    break;
+                }
+            }
+            self.segment_index = self.segments[self.current_segment].len() - 1;
+        }
+        self.segment_index -= n;
+    }
+
+    fn cw_by(&mut self, n: usize) {
+        self.segment_index += n;
+        while self.segment_index >= self.segments[self.current_segment].len() {
+            self.segment_index -= self.segments[self.current_segment].len();
+            self.current_segment = self.segments[self.current_segment].next_segment;
+        }
+    }
+
+    fn get(&self) -> u64 {
+        self.segments[self.current_segment].get(self.segment_index)
+    }
+
+    fn insert(&mut self, marble: u64) {
+        if self.segments[self.current_segment].needs_split() {
+            let old_next_segment = self.segments[self.current_segment].next_segment;
+            let split_to = self.segments[self.current_segment]
+                .split_off(self.current_segment, old_next_segment);
+            let split_index = self.allocate_segment(split_to);
+            self.segments[self.current_segment].next_segment = split_index;
+            self.segments[old_next_segment].prev_segment = split_index;
+
+            if self.segment_index >= self.segments[self.current_segment].len() {
+                self.segment_index -= self.segments[self.current_segment].len();
+                // This should be encapsulated better.
+                self.current_segment = self.segments[self.current_segment].next_segment;
+            }
+        }
+        self.segments[self.current_segment].insert(self.segment_index, marble);
+        self.len += 1;
+    }
+
+    // The marble clockwise of the removed one becomes current. When the removed marble was last
+    // in its segment, that's the first marble of the next segment.
+    fn remove(&mut self) {
+        self.segments[self.current_segment].remove(self.segment_index);
+        let current = self.current_segment;
+        let next_segment = self.segments[current].next_segment;
+        if self.segments[current].len() == 0 && next_segment != current {
+            self.unlink_segment(current);
+            self.current_segment = next_segment;
+            self.segment_index = 0;
+        } else if self.segment_index == self.segments[current].len() {
+            self.current_segment = next_segment;
+            self.segment_index = 0;
+        }
+        self.maybe_merge();
+        self.len -= 1;
+    }
+}
+
+impl<const N: usize> MarbleRing<N> {
+    // Walks the ring clockwise starting from the current marble, following the segment links
+    // around exactly once.
+    fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        let current = self.current_segment;
+        let marbles = self.segments[current].marbles();
+        let (before, after) = marbles.split_at(self.segment_index);
+        let others = iter::successors(Some(self.segments[current].next_segment), move |&i| {
+            Some(self.segments[i].next_segment)
+        })
+        .take_while(move |&i| i != current)
+        .flat_map(move |i| self.segments[i].marbles());
+        after.iter().chain(others).chain(before).cloned()
+    }
+
+    fn allocate_segment(&mut self, segment: MarbleSegment<N>) -> usize {
+        match self.free_segments.pop() {
+            Some(i) => {
+                self.segments[i] = segment;
+                i
+            }
+            None => {
+                self.segments.push(segment);
+                self.segments.len() - 1
+            }
+        }
+    }
+
+    fn unlink_segment(&mut self, i: usize) {
+        let MarbleSegment {
+            prev_segment,
+            next_segment,
+            ..
+        } = self.segments[i];
+        self.segments[prev_segment].next_segment = next_segment;
+        self.segments[next_segment].prev_segment = prev_segment;
+        self.free_segments.push(i);
+    }
+
+    // Merges `from` into the segment preceding it in the ring.
+    fn merge_into_prev(&mut self, from: usize) {
+        let into = self.segments[from].prev_segment;
+        let (into_segment, from_segment) = if into < from {
+            let (left, right) = self.segments.split_at_mut(from);
+            (&mut left[into], &right[0])
+        } else {
+            let (left, right) = self.segments.split_at_mut(into);
+            (&mut right[0], &left[from])
+        };
+        let into_len = into_segment.len();
+        into_segment.append(from_segment);
+        self.unlink_segment(from);
+        if self.current_segment == from {
+            self.current_segment = into;
+            self.segment_index += into_len;
+        }
+    }
+
+    fn maybe_merge(&mut self) {
+        let current = self.current_segment;
+        let MarbleSegment {
+            prev_segment,
+            next_segment,
+            ..
+        } = self.segments[current];
+        if !self.segments[current].is_sparse() {
+            return;
+        }
+        if prev_segment != current && self.segments[prev_segment].is_sparse() {
+            self.merge_into_prev(current);
+        } else if next_segment != current && self.segments[next_segment].is_sparse() {
+            self.merge_into_prev(next_segment);
+        }
+    }
+}
+
+impl<const N: usize> Debug for MarbleRing<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, marble) in self.iter().enumerate() {
+            if i == 0 {
+                write!(f, "*{}", marble)?;
+            } else {
+                write!(f, " {}", marble)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct MarbleGame<R = MarbleRing> {
+    last_marble: u64,
+    ring: R,
+}
+
+impl<R: Ring> MarbleGame<R> {
+    fn new() -> Self {
+        Self {
+            last_marble: 0,
+            ring: R::new(),
+        }
+    }
+
+    fn place_next(&mut self) -> u64 {
+        self.last_marble += 1;
+        if self.last_marble.is_multiple_of(23) {
+            self.ring.ccw_by(7);
+            let score = self.last_marble + self.ring.get();
+            self.ring.remove();
+            score
+        } else {
+            self.ring.cw_by(2);
+            self.ring.insert(self.last_marble);
+            0
+        }
+    }
+}
+
+// Straightforward ring with the current marble always at the front. Rotating costs time
+// proportional to the distance, which is always 2 or 7.
+struct VecDequeRing {
+    marbles: VecDeque<u64>,
+}
+
+impl Ring for VecDequeRing {
+    fn new() -> Self {
+        Self {
+            marbles: vec![0].into(),
+        }
+    }
+
+    fn cw_by(&mut self, n: usize) {
+        let n = n % self.marbles.len();
+        self.marbles.rotate_left(n);
+    }
+
+    fn ccw_by(&mut self, n: usize) {
+        let n = n % self.marbles.len();
+        self.marbles.rotate_right(n);
+    }
+
+    fn get(&self) -> u64 {
+        self.marbles[0]
+    }
+
+    fn insert(&mut self, marble: u64) {
+        self.marbles.push_front(marble);
+    }
+
+    fn remove(&mut self) {
+        self.marbles.pop_front();
+    }
+}
+
+// Plays `last_marble` marbles against the reference, comparing the current marble after every
+// placement.
+#[cfg(test)]
+fn check_against_reference<const N: usize>(last_marble: u64) {
+    let mut game = MarbleGame::<MarbleRing<N>>::new();
+    let mut reference = VecDequeRing::new();
+    for marble in 1..=last_marble {
+        let score = game.place_next();
+        let reference_score = if marble % 23 == 0 {
+            reference.ccw_by(7);
+            let score = marble + reference.get();
+            reference.remove();
+            score
+        } else {
+            reference.cw_by(2);
+            reference.insert(marble);
+            0
+        };
+        assert_eq!(score, reference_score, "score for marble {}", marble);
+        assert_eq!(
+            game.ring.get(),
+            reference.get(),
+            "current after marble {}",
+            marble
+        );
+    }
+}
+
+#[test]
+fn test_matches_reference() {
+    check_against_reference::<DEFAULT_SEGMENT_SIZE>(10000);
+    check_against_reference::<4>(10000);
+}
+
+// Applies the same pseudo-random moves, insertions and removals to a ring and the reference,
+// comparing the current marble after each. The ring grows for the first half, splitting segments,
+// and shrinks for the second, merging them.
+#[cfg(test)]
+fn check_random_ops<const N: usize>() {
+    let mut seed = 1u64;
+    let mut ring = MarbleRing::<N>::new();
+    let mut reference = VecDequeRing::new();
+    let mut next_marble = 1;
+    for step in 0..20000 {
+        seed = (seed * 1103515245 + 12345) % (1 << 31);
+        let n = (seed >> 16) as usize;
+        let (inserts, removes) = if step < 10000 { (4, 1) } else { (1, 4) };
+        let op = n % 8;
+        if op < inserts {
+            ring.insert(next_marble);
+            reference.insert(next_marble);
+            next_marble += 1;
+        } else if op < inserts + removes {
+            if reference.marbles.len() > 1 {
+                ring.remove();
+                reference.remove();
+            }
+        } else if op < 7 {
+            ring.cw_by(n / 8 % 40);
+            reference.cw_by(n / 8 % 40);
+        } else {
+            ring.ccw_by(n / 8 % 40);
+            reference.ccw_by(n / 8 % 40);
+        }
+        assert_eq!(
+            ring.get(),
+            reference.get(),
+            "segment size {}, step {}",
+            N,
+            step
+        );
+    }
+}
+
+#[test]
+fn test_random_ops() {
+    check_random_ops::<4>();
+    check_random_ops::<8>();
+    check_random_ops::<16>();
+    check_random_ops::<64>();
+    check_random_ops::<256>();
+    check_random_ops::<1024>();
+}
+
+#[test]
+fn test_cw_by_to_segment_end() {
+    // Moving exactly to the end of a segment lands on the first marble of the next one.
+    let mut ring = MarbleRing::<4>::new();
+    let mut reference = VecDequeRing::new();
+    for marble in 1..=10 {
+        ring.insert(marble);
+        reference.insert(marble);
+    }
+    for _ in 0..30 {
+        ring.cw_by(1);
+        reference.cw_by(1);
+        assert_eq!(ring.get(), reference.get());
+    }
+}
+
+#[test]
+fn test_iter() {
+    // The ring after marble 25 in the puzzle's example, with small segments so that it has been
+    // split several times.
+    let mut game = MarbleGame::<MarbleRing<4>>::new();
+    for _ in 0..9 {
+        game.place_next();
+    }
+    let marbles: Vec<u64> = game.ring.iter().collect();
+    assert_eq!(marbles, [9, 2, 5, 1, 6, 3, 7, 0, 8, 4]);
+    for _ in 9..25 {
+        game.place_next();
+    }
+    assert_eq!(
+        format!("{:?}", game.ring),
+        "*25 10 21 5 22 11 1 12 6 13 3 14 7 15 0 16 8 17 4 18 19 2 24 20"
+    );
+
+    let mut game = MarbleGame::<MarbleRing<4>>::new();
+    let mut reference = VecDequeRing::new();
+    for marble in 1..=1000 {
+        game.place_next();
+        if marble % 23 == 0 {
+            reference.ccw_by(7);
+            reference.remove();
+        } else {
+            reference.cw_by(2);
+            reference.insert(marble);
+        }
+        assert!(
+            game.ring.iter().eq(reference.marbles.iter().cloned()),
+            "after {}",
+            marble
+        );
+    }
+}
+
+#[test]
+fn test_remove_at_segment_boundary() {
+    let mut ring = MarbleRing::<4>::new();
+    let mut reference = VecDequeRing::new();
+    for marble in 1..=40 {
+        ring.cw_by(2);
+        reference.cw_by(2);
+        ring.insert(marble);
+        reference.insert(marble);
+    }
+
+    // Walk counter-clockwise, removing every marble that's last in its segment. The marble
+    // clockwise of it is then the first in the next segment, and must become current.
+    let mut boundary_removals = 0;
+    for _ in 0..40 {
+        let segment_len = ring.segments[ring.current_segment].len();
+        if ring.segment_index + 1 == segment_len && ring.len > segment_len {
+            ring.remove();
+            reference.remove();
+            boundary_removals += 1;
+        } else {
+            ring.ccw_by(1);
+            reference.ccw_by(1);
+        }
+        assert_eq!(ring.get(), reference.get());
+    }
+    assert!(boundary_removals > 0);
+}
+
+pub struct GameResult {
+    pub scores: Vec<u64>,
+}
+
+impl GameResult {
+    // Returns the 1-based number of the winning player and their score. Ties go to the lowest
+    // numbered player.
+    pub fn winner(&self) -> Option<(usize, u64)> {
+        let mut winner: Option<(usize, u64)> = None;
+        for (i, &score) in self.scores.iter().enumerate() {
+            if winner.is_none_or(|(_, best)| score > best) {
+                winner = Some((i + 1, score));
+            }
+        }
+        winner
+    }
+}
+
+pub fn play_game(num_players: usize, last_marble: u64) -> Result<GameResult> {
+    play_game_with_segment_size::<DEFAULT_SEGMENT_SIZE>(num_players, last_marble)
+}
+
+pub fn play_game_with_segment_size<const N: usize>(
+    num_players: usize,
+    last_marble: u64,
+) -> Result<GameResult> {
+    let mut results = play_game_checkpoints_with_segment_size::<N>(num_players, &[last_marble])?;
+    Ok(results.pop().unwrap())
+}
+
+// Plays a single game up to the last checkpoint, snapshotting the scores as each checkpoint's
+// marble is placed, so e.g. part 2 continues from where part 1 stopped.
+pub fn play_game_checkpoints(num_players: usize, checkpoints: &[u64]) -> Result<Vec<GameResult>> {
+    play_game_checkpoints_with_segment_size::<DEFAULT_SEGMENT_SIZE>(num_players, checkpoints)
+}
+
+pub fn play_game_checkpoints_with_segment_size<const N: usize>(
+    num_players: usize,
+    checkpoints: &[u64],
+) -> Result<Vec<GameResult>> {
+    play_checkpoints::<MarbleRing<N>>(num_players, checkpoints)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+    VecDeque,
+    Segmented,
+}
+
+impl FromStr for Engine {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "vecdeque" => Ok(Engine::VecDeque),
+            "segmented" => Ok(Engine::Segmented),
+            _ => Err(format!("unknown engine `{}`, expected vecdeque or segmented", s).into()),
+        }
+    }
+}
+
+pub fn play_game_checkpoints_with_engine(
+    engine: Engine,
+    num_players: usize,
+    checkpoints: &[u64],
+) -> Result<Vec<GameResult>> {
+    match engine {
+        Engine::VecDeque => play_checkpoints::<VecDequeRing>(num_players, checkpoints),
+        Engine::Segmented => play_checkpoints::<MarbleRing>(num_players, checkpoints),
+    }
+}
+
+fn play_checkpoints<R: Ring>(num_players: usize, checkpoints: &[u64]) -> Result<Vec<GameResult>> {
+    if num_players == 0 {
+        return Err("need players".into());
+    }
+    if checkpoints.windows(2).any(|w| w[0] > w[1]) {
+        return Err("checkpoints must be in ascending order".into());
+    }
+    let mut game = MarbleGame::<R>::new();
+    let mut scores = vec![0; num_players];
+    let mut results = Vec::with_capacity(checkpoints.len());
+    let mut turn = 0;
+    for &checkpoint in checkpoints {
+        while game.last_marble < checkpoint {
+            scores[turn] += game.place_next();
+            turn = (turn + 1) % num_players;
+        }
+        results.push(GameResult {
+            scores: scores.clone(),
+        });
+    }
+    Ok(results)
+}
+
+// Checks the documented behaviour of each `Ring` operation, including moves further than the
+// number of marbles.
+#[cfg(test)]
+fn check_ring<R: Ring>() {
+    let mut ring = R::new();
+    assert_eq!(ring.get(), 0);
+    for marble in 1..=4 {
+        ring.insert(marble);
+    }
+    // Clockwise from the current marble: 4 3 2 1 0.
+    assert_eq!(ring.get(), 4);
+    ring.cw_by(1);
+    assert_eq!(ring.get(), 3);
+    ring.cw_by(4);
+    assert_eq!(ring.get(), 4);
+    ring.ccw_by(1);
+    assert_eq!(ring.get(), 0);
+    ring.ccw_by(7);
+    assert_eq!(ring.get(), 2);
+    ring.remove();
+    assert_eq!(ring.get(), 1);
+    ring.cw_by(2);
+    assert_eq!(ring.get(), 4);
+}
+
+#[test]
+fn test_ring_impls() {
+    check_ring::<VecDequeRing>();
+    check_ring::<MarbleRing<4>>();
+    check_ring::<MarbleRing>();
+}
+
+// Examples from the puzzle, then scores recorded from the implementation that never reclaimed
+// empty segments.
+#[cfg(test)]
+fn check_high_scores<R: Ring>() -> Result<()> {
+    let games = [
+        (9, 25, 32),
+        (10, 1618, 8317),
+        (13, 7999, 146373),
+        (17, 1104, 2764),
+        (21, 6111, 54718),
+        (30, 5807, 37305),
+        (7, 100000, 40808568),
+        (429, 70901, 399645),
+    ];
+    for &(num_players, last_marble, expected) in &games {
+        let results = play_checkpoints::<R>(num_players, &[last_marble])?;
+        assert_eq!(results[0].winner().unwrap().1, expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_high_score() -> Result<()> {
+    check_high_scores::<VecDequeRing>()?;
+    check_high_scores::<MarbleRing<4>>()?;
+    check_high_scores::<MarbleRing>()
+}
+
+#[test]
+fn test_segment_sizes() -> Result<()> {
+    let expected = play_game(7, 100000)?.scores;
+    assert_eq!(
+        play_game_with_segment_size::<8>(7, 100000)?.scores,
+        expected
+    );
+    assert_eq!(
+        play_game_with_segment_size::<16>(7, 100000)?.scores,
+        expected
+    );
+    assert_eq!(
+        play_game_with_segment_size::<256>(7, 100000)?.scores,
+        expected
+    );
+    assert_eq!(
+        play_game_with_segment_size::<1024>(7, 100000)?.scores,
+        expected
+    );
+    Ok(())
+}
+
+#[test]
+fn test_checkpoints() -> Result<()> {
+    let results = play_game_checkpoints(13, &[25, 1618, 1618, 7999])?;
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].scores, play_game(13, 25)?.scores);
+    assert_eq!(results[1].scores, play_game(13, 1618)?.scores);
+    assert_eq!(results[2].scores, results[1].scores);
+    assert_eq!(results[3].scores, play_game(13, 7999)?.scores);
+    assert_eq!(results[3].winner().unwrap().1, 146373);
+    assert!(play_game_checkpoints(13, &[100, 25]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_no_overflow() -> Result<()> {
+    // A lone player takes every score, which passes u32::MAX well before a million marbles. Each
+    // turn scores at most twice the last marble, bounding the total.
+    let last_marble = 1_000_000;
+    let (_, score) = play_game(1, last_marble)?.winner().unwrap();
+    assert!(score > u64::from(u32::MAX));
+    assert!(score <= 2 * last_marble * (last_marble / 23));
+
+    let mut reference = VecDequeRing::new();
+    let mut expected = 0;
+    for marble in 1..=last_marble {
+        if marble % 23 == 0 {
+            reference.ccw_by(7);
+            expected += marble + reference.get();
+            reference.remove();
+        } else {
+            reference.cw_by(2);
+            reference.insert(marble);
+        }
+    }
+    assert_eq!(score, expected);
+    Ok(())
+}
+
+#[test]
+fn test_engines() -> Result<()> {
+    let vecdeque = play_game_checkpoints_with_engine(Engine::VecDeque, 13, &[7999, 100000])?;
+    let segmented = play_game_checkpoints_with_engine(Engine::Segmented, 13, &[7999, 100000])?;
+    assert_eq!(vecdeque[0].winner().unwrap().1, 146373);
+    assert_eq!(vecdeque[1].winner(), segmented[1].winner());
+    assert_eq!(vecdeque[1].scores, segmented[1].scores);
+    assert_eq!("vecdeque".parse::<Engine>()?, Engine::VecDeque);
+    assert!("linked".parse::<Engine>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_winner() -> Result<()> {
+    assert_eq!(play_game(9, 25)?.winner(), Some((5, 32)));
+    let tie = GameResult {
+        scores: vec![3, 7, 1, 7],
+    };
+    assert_eq!(tie.winner(), Some((2, 7)));
+    assert_eq!(GameResult { scores: vec![0, 0] }.winner(), Some((1, 0)));
+    assert!(play_game(0, 25).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_segments_reclaimed() {
+    let mut game: MarbleGame = MarbleGame::new();
+    for _ in 0..100000 {
+        game.place_next();
+        let ring = &game.ring;
+        let mut segment = ring.current_segment;
+        let mut linked = 0;
+        loop {
+            assert!(ring.segments[segment].len() > 0);
+            linked += 1;
+            segment = ring.segments[segment].next_segment;
+            if segment == ring.current_segment {
+                break;
+            }
+        }
+        assert_eq!(linked + ring.free_segments.len(), ring.segments.len());
+    }
+}
+
+struct StrParser<'a> {
+    it: Peekable<Chars<'a>>,
+}
+
+impl<'a> StrParser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            it: s.chars().peekable(),
+        }
+    }
+
+    fn done(&mut self) -> bool {
+        self.it.peek().is_none()
+    }
+
+    fn consume_str(&mut self, s: &str) -> Result<()> {
+        let orig_iter = self.it.clone();
+        for c in s.chars() {
+            if self.it.next() != Some(c) {
+                self.it = orig_iter;
+                return Err(format!("expected `{}`", s).into());
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_usize(&mut self, what: &str) -> Result<usize> {
+        let mut digits = String::new();
+        while let Some(&c) = self.it.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.it.next();
+        }
+        digits
+            .parse()
+            .map_err(|_| format!("expected {}", what).into())
+    }
+}
+
+// Parses the puzzle input, e.g. "419 players; last marble is worth 72164 points".
+pub fn parse_sentence(s: &str) -> Result<(usize, u64)> {
+    let mut parser = StrParser::new(s.trim());
+    let num_players = parser.parse_usize("number of players")?;
+    parser.consume_str(" players; last marble is worth ")?;
+    let last_marble = parser.parse_usize("last marble")? as u64;
+    parser.consume_str(" points")?;
+    if !parser.done() {
+        return Err("unexpected trailing input".into());
+    }
+    Ok((num_players, last_marble))
+}
+
+#[test]
+fn test_parse_sentence() -> Result<()> {
+    assert_eq!(
+        parse_sentence("419 players; last marble is worth 72164 points")?,
+        (419, 72164)
+    );
+    assert_eq!(
+        parse_sentence("9 players; last marble is worth 25 points\n")?,
+        (9, 25)
+    );
+    let err = |s| parse_sentence(s).unwrap_err().to_string();
+    assert_eq!(
+        err("9 players; last marble is worth 25"),
+        "expected ` points`"
+    );
+    assert_eq!(
+        err("9 players; last marble is worth 25 pts"),
+        "expected ` points`"
+    );
+    assert_eq!(
+        err("players; last marble is worth 25 points"),
+        "expected number of players"
+    );
+    assert_eq!(
+        err("9 players, last marble is worth 25 points"),
+        "expected ` players; last marble is worth `"
+    );
+    assert_eq!(
+        err("9 players; last marble is worth lots points"),
+        "expected last marble"
+    );
+    assert_eq!(
+        err("9 players; last marble is worth 25 points!"),
+        "unexpected trailing input"
+    );
+    Ok(())
+}
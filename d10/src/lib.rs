@@ -0,0 +1,491 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::io::{self, Write};
+use std::result;
+use std::str::FromStr;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+// Strips `prefix` from `s`, ignoring leading whitespace.
+fn consume_str<'a>(s: &'a str, prefix: &str) -> Result<&'a str> {
+    s.trim_start()
+        .strip_prefix(prefix)
+        .ok_or_else(|| format!("expected `{}` at `{}`", prefix, s.trim()).into())
+}
+
+// Parses an integer allowing whitespace around it and between the sign and the digits.
+fn parse_i32(s: &str, field: &str) -> Result<i32> {
+    let trimmed = s.trim();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits.trim_start()),
+        None => (false, trimmed),
+    };
+    if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(format!("invalid {} `{}`", field, trimmed).into());
+    }
+    let magnitude: i32 = digits
+        .parse()
+        .map_err(|e| format!("invalid {}: {}", field, e))?;
+    Ok(if negative { -magnitude } else { magnitude })
+}
+
+// Parses `name=<x, y>` from the start of `s`, returning the pair and the rest of the input.
+fn parse_vector<'a>(s: &'a str, name: &str) -> Result<((i32, i32), &'a str)> {
+    let s = consume_str(s, name)?;
+    let s = consume_str(s, "=")?;
+    let s = consume_str(s, "<")?;
+    let end = s
+        .find('>')
+        .ok_or_else(|| format!("unterminated {}", name))?;
+    let mut parts = s[..end].split(',');
+    let (x, y) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(x), Some(y), None) => (x, y),
+        _ => return Err(format!("expected 2 coordinates in {}", name).into()),
+    };
+    let x = parse_i32(x, &format!("{} x", name))?;
+    let y = parse_i32(y, &format!("{} y", name))?;
+    Ok(((x, y), &s[end + 1..]))
+}
+
+pub struct Star {
+    initial: (i32, i32),
+    velocity: (i32, i32),
+}
+
+impl Star {
+    // Widened so that far-off times can't overflow.
+    fn at(&self, t: i64) -> (i64, i64) {
+        let (ix, iy) = self.initial;
+        let (vx, vy) = self.velocity;
+        (ix as i64 + t * vx as i64, iy as i64 + t * vy as i64)
+    }
+}
+
+impl FromStr for Star {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Star> {
+        let (initial, rest) = parse_vector(s, "position")?;
+        let (velocity, rest) = parse_vector(rest, "velocity")?;
+        if !rest.trim().is_empty() {
+            return Err(format!("trailing input `{}`", rest.trim()).into());
+        }
+        Ok(Star { initial, velocity })
+    }
+}
+
+// The extremes of a set of points, as (xmin, ymin, xmax, ymax).
+pub type Bounds = (i64, i64, i64, i64);
+
+fn bounds_of<I: IntoIterator<Item = (i64, i64)>>(points: I) -> Option<Bounds> {
+    let mut points = points.into_iter();
+    let (x, y) = points.next()?;
+    Some(
+        points.fold((x, y, x, y), |(xmin, ymin, xmax, ymax), (x, y)| {
+            (xmin.min(x), ymin.min(y), xmax.max(x), ymax.max(y))
+        }),
+    )
+}
+
+pub fn linear_size((xmin, ymin, xmax, ymax): Bounds) -> i64 {
+    (xmax - xmin) + (ymax - ymin)
+}
+
+pub fn area((xmin, ymin, xmax, ymax): Bounds) -> i64 {
+    (xmax - xmin) * (ymax - ymin)
+}
+
+pub enum Metric {
+    Area,
+    Linear,
+}
+
+impl FromStr for Metric {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Metric> {
+        match s {
+            "area" => Ok(Metric::Area),
+            "linear" => Ok(Metric::Linear),
+            _ => Err(format!("unknown metric `{}`, expected area or linear", s).into()),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Constellation {
+    stars: Vec<Star>,
+    // The search revisits times, so bounds are kept for every time evaluated so far.
+    bounds: RefCell<HashMap<i64, Bounds>>,
+    passes: Cell<usize>,
+}
+
+impl Constellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, star: Star) {
+        self.stars.push(star);
+        self.bounds.get_mut().clear();
+    }
+
+    pub fn positions(&self, t: i64) -> Vec<(i64, i64)> {
+        self.stars.iter().map(|s| s.at(t)).collect()
+    }
+
+    pub fn bounds_at(&self, t: i64) -> Bounds {
+        if let Some(&bounds) = self.bounds.borrow().get(&t) {
+            return bounds;
+        }
+        self.passes.set(self.passes.get() + 1);
+        // Without stars there's nothing to take up room.
+        let bounds = bounds_of(self.stars.iter().map(|s| s.at(t))).unwrap_or((0, 0, 0, 0));
+        self.bounds.borrow_mut().insert(t, bounds);
+        bounds
+    }
+
+    // How many times the stars have been walked to find their bounds.
+    pub fn passes(&self) -> usize {
+        self.passes.get()
+    }
+
+    // No star can still be closing in on the others once it has had time to cross the largest
+    // initial coordinate at the slowest non-zero speed, so the smallest box is before then.
+    pub fn max_time(&self) -> i64 {
+        let max_coord = self
+            .stars
+            .iter()
+            .flat_map(|s| vec![s.initial.0, s.initial.1])
+            .map(|c| (c as i64).abs())
+            .max()
+            .unwrap_or(0);
+        let min_speed = self
+            .stars
+            .iter()
+            .flat_map(|s| vec![s.velocity.0, s.velocity.1])
+            .filter(|&v| v != 0)
+            .map(|v| (v as i64).abs())
+            .min();
+        match min_speed {
+            Some(min_speed) => 2 * max_coord / min_speed + 2,
+            None => 0,
+        }
+    }
+
+    pub fn is_local_min<M: Fn(Bounds) -> i64>(&self, metric: M, t: i64) -> bool {
+        let size = |t| metric(self.bounds_at(t));
+        let min = size(t);
+        (t == 0 || size(t - 1) >= min) && size(t + 1) >= min
+    }
+
+    // Binary searches for the time when `metric` of the stars' positions is smallest.
+    pub fn converge_time<M: Fn(Bounds) -> i64>(&self, metric: M) -> i64 {
+        let size = |t| metric(self.bounds_at(t));
+        let (mut tmin, mut tmax) = (0, self.max_time());
+        while tmin != tmax {
+            let tmid = (tmax + tmin) / 2;
+            if size(tmid) > size(tmid + 1) {
+                tmin = tmid + 1;
+            } else {
+                tmax = tmid;
+            }
+        }
+        if self.is_local_min(&metric, tmin) {
+            return tmin;
+        }
+
+        // The size wasn't unimodal, so scan near where the search ended instead.
+        const SCAN_RADIUS: i64 = 1000;
+        ((tmin - SCAN_RADIUS).max(0)..=tmin + SCAN_RADIUS)
+            .min_by_key(|&t| size(t))
+            .unwrap()
+    }
+}
+
+impl FromStr for Constellation {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Constellation> {
+        let mut constellation = Constellation::new();
+        for (i, line) in s.lines().enumerate() {
+            let star = line.parse().map_err(|e| format!("line {}: {}", i + 1, e))?;
+            constellation.push(star);
+        }
+        if constellation.stars.is_empty() {
+            return Err("no points".into());
+        }
+        Ok(constellation)
+    }
+}
+
+// Finds the second the stars spell out their message, by smallest area, and draws it.
+pub fn solve(input: &str) -> Result<(i32, String)> {
+    let constellation: Constellation = input.parse()?;
+    let t = constellation.converge_time(area);
+    Ok((i32::try_from(t)?, render(&constellation.positions(t))))
+}
+
+#[test]
+fn test_converge_time() -> Result<()> {
+    // Slow stars that form a small cross at t = 3,000,000, well past a search bound of 1 << 20.
+    let t = 3_000_000;
+    let targets = [(0, 0), (1, 0), (-1, 0), (0, 1), (0, -1)];
+    let velocities = [(1, 1), (-1, 1), (1, -1), (-1, -1), (0, 1)];
+    let mut constellation = Constellation::new();
+    for (&(x, y), &(vx, vy)) in targets.iter().zip(velocities.iter()) {
+        let line = format!(
+            "position=<{}, {}> velocity=<{}, {}>",
+            x - t * vx,
+            y - t * vy,
+            vx,
+            vy
+        );
+        constellation.push(line.parse()?);
+    }
+    assert!(constellation.max_time() > t as i64);
+    assert_eq!(constellation.converge_time(linear_size), t as i64);
+    assert_eq!(constellation.converge_time(area), t as i64);
+    assert!(constellation.is_local_min(linear_size, t as i64));
+
+    let mut still = Constellation::new();
+    still.push("position=<3, -2> velocity=<0, 0>".parse()?);
+    assert_eq!(still.converge_time(linear_size), 0);
+    Ok(())
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = include_str!("../example");
+
+#[test]
+fn test_solve() -> Result<()> {
+    let (t, message) = solve(EXAMPLE)?;
+    assert_eq!(t, 3);
+    let expected = [
+        "*   *  ***",
+        "*   *   * ",
+        "*   *   * ",
+        "*****   * ",
+        "*   *   * ",
+        "*   *   * ",
+        "*   *   * ",
+        "*   *  ***",
+    ];
+    assert_eq!(message.lines().collect::<Vec<_>>(), expected);
+
+    let err = solve("position=<1, 2> velocity=<0, 0>\nposition=<1, 2>").unwrap_err();
+    assert_eq!(err.to_string(), "line 2: expected `velocity` at ``");
+    assert_eq!(solve("").unwrap_err().to_string(), "no points");
+    assert!(write_pbm(&[], &mut Vec::new()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_passes() -> Result<()> {
+    let example: Constellation = EXAMPLE.parse()?;
+    assert_eq!(example.converge_time(linear_size), 3);
+    assert_eq!(example.converge_time(area), 3);
+
+    // Many stars converging on a 100x100 square at t = 10,000. Each evaluated time should cost
+    // one pass over them, however often the search comes back to it.
+    let t = 10_000;
+    let mut seed = 1u64;
+    let mut next = |n: u64| {
+        seed = (seed * 1103515245 + 12345) % (1 << 31);
+        (seed >> 8) % n
+    };
+    let mut large = Constellation::new();
+    for _ in 0..20_000 {
+        let (x, y) = (next(100) as i32, next(100) as i32);
+        let (vx, vy) = (next(21) as i32 - 10, next(21) as i32 - 10);
+        large.push(Star {
+            initial: (x - t * vx, y - t * vy),
+            velocity: (vx, vy),
+        });
+    }
+    assert_eq!(large.converge_time(linear_size), t as i64);
+    let passes = large.passes();
+    let halvings = 64 - large.max_time().leading_zeros() as usize;
+    assert!(passes <= 2 * halvings + 3, "{} passes", passes);
+    assert_eq!(large.converge_time(area), t as i64);
+    assert_eq!(large.passes(), passes);
+    Ok(())
+}
+
+// Draws the bounding box of `points` a line at a time, with `*` for each occupied position.
+pub fn render(points: &[(i64, i64)]) -> String {
+    let (xmin, ymin, xmax, ymax) = match bounds_of(points.iter().copied()) {
+        Some(bounds) => bounds,
+        None => return String::new(),
+    };
+    let occupied: HashSet<_> = points.iter().collect();
+    let mut out = String::new();
+    for y in ymin..=ymax {
+        for x in xmin..=xmax {
+            out.push(if occupied.contains(&(x, y)) { '*' } else { ' ' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Renders each second from `n` before `t` to `n` after it, under a header giving its size.
+pub fn render_window<M: Fn(Bounds) -> i64>(
+    constellation: &Constellation,
+    metric: M,
+    t: i64,
+    n: i64,
+) -> String {
+    let mut out = String::new();
+    for t in (t - n).max(0)..=t + n {
+        let pos = constellation.positions(t);
+        out.push_str(&format!(
+            "t = {} (size = {})\n",
+            t,
+            metric(constellation.bounds_at(t))
+        ));
+        out.push_str(&render(&pos));
+    }
+    out
+}
+
+// Writes `points` as a plain PBM bitmap, one pixel per cell, with a blank border of 2 cells.
+pub fn write_pbm<W: Write>(points: &[(i64, i64)], out: &mut W) -> io::Result<()> {
+    const BORDER: i64 = 2;
+    // Plain PBM asks for lines of at most 70 characters.
+    const MAX_LINE: usize = 70;
+    let (xmin, ymin, xmax, ymax) = bounds_of(points.iter().copied())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no points"))?;
+    let occupied: HashSet<_> = points.iter().collect();
+    let (width, height) = (xmax - xmin + 1 + 2 * BORDER, ymax - ymin + 1 + 2 * BORDER);
+    writeln!(out, "P1")?;
+    writeln!(out, "{} {}", width, height)?;
+    for y in ymin - BORDER..=ymax + BORDER {
+        let row: Vec<u8> = (xmin - BORDER..=xmax + BORDER)
+            .map(|x| {
+                if occupied.contains(&(x, y)) {
+                    b'1'
+                } else {
+                    b'0'
+                }
+            })
+            .collect();
+        for chunk in row.chunks(MAX_LINE) {
+            out.write_all(chunk)?;
+            out.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_write_pbm() -> Result<()> {
+    let mut out = Vec::new();
+    write_pbm(&[(10, -3), (12, -2)], &mut out)?;
+    let pbm = String::from_utf8(out)?;
+    let mut lines = pbm.lines();
+    assert_eq!(lines.next(), Some("P1"));
+    assert_eq!(lines.next(), Some("7 6"));
+    let rows: Vec<_> = lines.collect();
+    assert_eq!(rows.len(), 6);
+    assert!(rows.iter().all(|row| row.len() == 7));
+    assert_eq!(rows[2], "0010000");
+    assert_eq!(rows[3], "0000100");
+    assert_eq!(rows[0], "0000000");
+
+    let mut out = Vec::new();
+    write_pbm(&[(0, 0), (99, 0)], &mut out)?;
+    let pbm = String::from_utf8(out)?;
+    assert!(pbm.starts_with("P1\n104 5\n"));
+    assert!(pbm.lines().all(|line| line.len() <= 70));
+    Ok(())
+}
+
+#[test]
+fn test_parse_star() -> Result<()> {
+    let lines = [
+        "position=< 9,  1> velocity=< 0,  2>",
+        "position=<9,1> velocity=<0,2>",
+        "position=<  9 , 1 >   velocity=<0, 2>",
+        "position=<9,\t1>\tvelocity=<\t0,2>",
+        "  position = < 9, 1 > velocity = < 0, 2 >  ",
+        "position=<9, 1>velocity=<0, 2>",
+    ];
+    for line in &lines {
+        let star: Star = line.parse()?;
+        assert_eq!((star.initial, star.velocity), ((9, 1), (0, 2)), "{}", line);
+    }
+    let star: Star = "position=<- 3, -11> velocity=<-1,  -2>".parse()?;
+    assert_eq!((star.initial, star.velocity), ((-3, -11), (-1, -2)));
+
+    let malformed = [
+        ("position=<9, x> velocity=<0, 2>", "invalid position y `x`"),
+        (
+            "position=<9, 1 2> velocity=<0, 2>",
+            "invalid position y `1 2`",
+        ),
+        (
+            "position=<9, 1> velocity=<0, 2, 3>",
+            "expected 2 coordinates in velocity",
+        ),
+        ("position=<9, 1> velocity=<0, 2", "unterminated velocity"),
+        (
+            "position=<9, 1> speed=<0, 2>",
+            "expected `velocity` at `speed=<0, 2>`",
+        ),
+        ("position=<9, 1> velocity=<0, -> ", "invalid velocity y `-`"),
+        ("position=<9, 1> velocity=<0, 2> !", "trailing input `!`"),
+    ];
+    for &(line, err) in &malformed {
+        assert_eq!(line.parse::<Star>().err().expect(line).to_string(), err);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_render_window() -> Result<()> {
+    let mut constellation = Constellation::new();
+    constellation.push("position=<-1, 0> velocity=<1, 0>".parse()?);
+    constellation.push("position=<3, 0> velocity=<-1, 0>".parse()?);
+    constellation.push("position=<1, 1> velocity=<0, 0>".parse()?);
+    assert_eq!(
+        render_window(&constellation, linear_size, 1, 1),
+        "t = 0 (size = 5)\n*   *\n  *  \nt = 1 (size = 3)\n* *\n * \nt = 2 (size = 1)\n*\n*\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_render() {
+    let points = [
+        (-2, 5),
+        (0, 5),
+        (-2, 6),
+        (-1, 6),
+        (0, 6),
+        (-2, 7),
+        (0, 7),
+        (2, 5),
+        (2, 6),
+        (2, 7),
+    ];
+    assert_eq!(render(&points), "* * *\n*** *\n* * *\n");
+    assert_eq!(render(&[(3, 3), (3, 3)]), "*\n");
+    assert_eq!(render(&[]), "");
+}
+
+#[test]
+fn test_metrics() -> Result<()> {
+    // Width shrinks then grows as |7 - 3t| while height is |16 - 12t|. At t = 1 the box is 4x4,
+    // smallest by width plus height, but at t = 2 it's 1x8 and covers half the area.
+    let mut constellation = Constellation::new();
+    constellation.push("position=<7, 0> velocity=<-3, 0>".parse()?);
+    constellation.push("position=<0, 0> velocity=<0, 0>".parse()?);
+    constellation.push("position=<0, 16> velocity=<0, -12>".parse()?);
+    assert_eq!(constellation.converge_time(linear_size), 1);
+    assert_eq!(constellation.converge_time(area), 2);
+    assert!("volume".parse::<Metric>().is_err());
+    Ok(())
+}
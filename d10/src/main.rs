@@ -1,131 +1,54 @@
-use std::io::{self, BufRead};
-use std::str::{self, FromStr};
-use std::result;
-use std::error::Error;
-use std::iter::Peekable;
+use d10::{
+    area, linear_size, render, render_window, solve, write_pbm, Constellation, Metric, Result,
+};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
 
-type Result<T> = result::Result<T, Box<Error>>;
-
-fn consume_str<T: Iterator<Item = u8>>(it: &mut T, s: &[u8]) -> Result<()> {
-    for &c in s {
-        match it.next() {
-            Some(x) if x == c => {},
-            _ => return Err("parse failed".into()),
-        }
-    }
-    Ok(())
-}
-
-fn parse_i32<T: Iterator<Item = u8>>(it: &mut Peekable<T>) -> Result<i32> {
-    let mut num = Vec::new();
-    while let Some(&c) = it.peek() {
-        if c == b' ' {
-            // noop
-        } else if c == b'-' || c.is_ascii_digit() {
-            num.push(c);
-        } else {
-            break;
-        }
-        it.next();
-    }
-    unsafe {
-        Ok(str::from_utf8_unchecked(&num).parse()?)
-    }
-}
-
-struct Star {
-    initial: (i32, i32),
-    velocity: (i32, i32),
-}
-
-impl Star {
-    fn at(&self, t: i32) -> (i32, i32) {
-        let (ix, iy) = self.initial;
-        let (vx, vy) = self.velocity;
-        (ix + t * vx, iy + t * vy)
-    }
-}
-
-impl FromStr for Star {
-    type Err = Box<Error>;
-
-    fn from_str(s: &str) -> Result<Star> {
-        let mut it = s.bytes().peekable();
-        consume_str(&mut it, b"position=<")?;
-        let ix = parse_i32(&mut it)?;
-        consume_str(&mut it, b",")?;
-        let iy = parse_i32(&mut it)?;
-        consume_str(&mut it, b"> velocity=<")?;
-        let vx = parse_i32(&mut it)?;
-        consume_str(&mut it, b",")?;
-        let vy = parse_i32(&mut it)?;
-        consume_str(&mut it, b">")?;
-        if it.peek() != None {
-            return Err("trailing input".into());
+fn main() -> Result<()> {
+    let mut metric = Metric::Area;
+    let mut window = None;
+    let mut render_file = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--metric" => metric = args.next().ok_or("missing metric")?.parse()?,
+            "--window" => window = Some(args.next().ok_or("missing window size")?.parse()?),
+            "--render-file" => render_file = Some(args.next().ok_or("missing render file")?),
+            _ => return Err(format!("unexpected argument {}", arg).into()),
         }
-        Ok(Star { initial: (ix, iy), velocity: (vx, vy) })
     }
-}
-
-fn bounds_of(pos: &Vec<(i32, i32)>) -> (i32, i32, i32, i32) {
-    let xmin = pos.iter().map(|&(x, _)| x).min().unwrap();
-    let xmax = pos.iter().map(|&(x, _)| x).max().unwrap();
-    let ymin = pos.iter().map(|&(_, y)| y).min().unwrap();
-    let ymax = pos.iter().map(|&(_, y)| y).max().unwrap();
-    (xmin, ymin, xmax, ymax)
-}
 
-struct Constellation {
-    stars: Vec<Star>,
-}
-
-impl Constellation {
-    fn new() -> Self {
-        Self { stars: Vec::new() }
-    }
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
 
-    fn push(&mut self, star: Star) {
-        self.stars.push(star);
+    // The plain answer comes from the same `solve` the tests check.
+    if matches!(metric, Metric::Area) && window.is_none() && render_file.is_none() {
+        let (time, message) = solve(&input)?;
+        print!("{}", message);
+        println!("{}", time);
+        return Ok(());
     }
 
-    fn linear_size(&self, t: i32) -> i32 {
-        let pos: Vec<_> = self.stars.iter().map(|s| s.at(t)).collect();
-        let (xmin, ymin, xmax, ymax) = bounds_of(&pos);
-        (xmax - xmin) + (ymax - ymin)
-    }
-}
+    let constellation: Constellation = input.parse()?;
 
-fn main() -> Result<()> {
-    let mut constellation = Constellation::new();
-    for line in io::stdin().lock().lines() {
-        constellation.push(line?.parse()?);
-    }
+    let metric = match metric {
+        Metric::Area => area,
+        Metric::Linear => linear_size,
+    };
+    let tmin = constellation.converge_time(metric);
 
-    // Binary search for smallest bounding box.
-    let (mut tmin, mut tmax) = (0, 1 << 20);
-    while tmin != tmax {
-        let tmid = (tmax + tmin) / 2;
-        if constellation.linear_size(tmid) > constellation.linear_size(tmid + 1) {
-            tmin = tmid + 1;
-        } else {
-            tmax = tmid;
-        }
+    match window {
+        Some(n) => print!("{}", render_window(&constellation, metric, tmin, n)),
+        None => print!("{}", render(&constellation.positions(tmin))),
     }
+    println!("{}", tmin);
 
-    let pos: Vec<_> = constellation.stars.iter().map(|s| s.at(tmin)).collect();
-    let (xmin, ymin, xmax, ymax) = bounds_of(&pos);
-    for y in ymin..=ymax {
-        for x in xmin..=xmax {
-            if pos.contains(&(x, y)) {
-                print!("*");
-            } else {
-                print!(" ");
-            }
-        }
-        println!();
+    if let Some(path) = render_file {
+        let mut out = BufWriter::new(File::create(path)?);
+        write_pbm(&constellation.positions(tmin), &mut out)?;
+        out.flush()?;
     }
 
-    println!("{}", tmin);
-
     Ok(())
 }
@@ -0,0 +1,537 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::result;
+use std::str::FromStr;
+use std::thread;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+pub const DEFAULT_WIDTH: usize = 300;
+pub const DEFAULT_HEIGHT: usize = 300;
+
+#[derive(Clone, Copy)]
+pub struct PowerCell(pub i32);
+
+impl PowerCell {
+    // The intermediate product passes i32::MAX once x and y are past about 1450, so it's worked
+    // out in i64 and only the hundreds digit is narrowed.
+    pub fn generate(x: usize, y: usize, serial_num: i32) -> Self {
+        let (x, y) = (x as i64, y as i64);
+        let rack_id = x + 10;
+        let mut result = rack_id * y;
+        result += i64::from(serial_num);
+        result *= rack_id;
+        let digit = (result / 100) % 10;
+        Self(digit as i32 - 5)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Square {
+    pub power: i64,
+    pub x: usize,
+    pub y: usize,
+    pub size: usize,
+}
+
+impl Square {
+    // Squares rank by power. Among equally powerful ones the smallest wins, and after that the
+    // one with the smallest x, then the smallest y.
+    fn rank(&self) -> (i64, Reverse<usize>, Reverse<usize>, Reverse<usize>) {
+        (
+            self.power,
+            Reverse(self.size),
+            Reverse(self.x),
+            Reverse(self.y),
+        )
+    }
+}
+
+impl Ord for Square {
+    fn cmp(&self, other: &Square) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Square {
+    fn partial_cmp(&self, other: &Square) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Keeps the `k` best of `squares`, best first.
+fn top_k<I: IntoIterator<Item = Square>>(squares: I, k: usize) -> Vec<Square> {
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for square in squares {
+        heap.push(Reverse(square));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+    let mut best: Vec<_> = heap.into_iter().map(|Reverse(square)| square).collect();
+    best.sort_by(|a, b| b.cmp(a));
+    best
+}
+
+// A rectangle of cells, written `x,y,width,height` with (x, y) its top left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl FromStr for Region {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Region> {
+        let values: Vec<usize> = s
+            .split(',')
+            .map(|v| v.trim().parse())
+            .collect::<result::Result<_, _>>()
+            .map_err(|e| format!("invalid region `{}`: {}", s, e))?;
+        match values.as_slice() {
+            &[x, y, width, height] if width > 0 && height > 0 => Ok(Region {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!(
+                "expected a non-empty `x,y,width,height` region, got `{}`",
+                s
+            )
+            .into()),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+// Summed-area table of the power cells, with a row and column of zeros before the first cell.
+pub struct Grid {
+    width: usize,
+    height: usize,
+    sums: Vec<i64>,
+}
+
+impl Grid {
+    pub fn from_serial_num(serial_num: i32, width: usize, height: usize) -> Self {
+        Self::from_powers(width, height, |x, y| {
+            PowerCell::generate(x, y, serial_num).0
+        })
+    }
+
+    pub fn from_powers<F: Fn(usize, usize) -> i32>(width: usize, height: usize, power: F) -> Self {
+        let mut grid = Self {
+            width,
+            height,
+            sums: vec![0; (width + 1) * (height + 1)],
+        };
+        for y in 1..=height {
+            for x in 1..=width {
+                let sum = grid.sum(x - 1, y) + grid.sum(x, y - 1) - grid.sum(x - 1, y - 1)
+                    + i64::from(power(x, y));
+                let i = grid.index(x, y);
+                grid.sums[i] = sum;
+            }
+        }
+        grid
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        y * (self.width + 1) + x
+    }
+
+    // Total power of the cells up to and including (x, y).
+    fn sum(&self, x: usize, y: usize) -> i64 {
+        self.sums[self.index(x, y)]
+    }
+
+    // Recovers a single cell from the sums around it.
+    pub fn power_cell(&self, x: usize, y: usize) -> PowerCell {
+        let power =
+            self.sum(x, y) - self.sum(x - 1, y) - self.sum(x, y - 1) + self.sum(x - 1, y - 1);
+        PowerCell(power as i32)
+    }
+
+    // Draws the cells of `region` with one character per power level from -5 to 4, darkest for
+    // the most power. Cells of `marker` that fall inside the region are drawn as `X` instead.
+    pub fn heatmap(&self, region: &Region, marker: Option<&Square>) -> Result<String> {
+        const RAMP: &[u8] = b" .:-=+*#%@";
+        if region.x == 0
+            || region.y == 0
+            || region.x + region.width - 1 > self.width
+            || region.y + region.height - 1 > self.height
+        {
+            return Err(format!("region {} is outside the grid", region).into());
+        }
+        let (rows, columns) = match marker {
+            Some(m) => (m.y..m.y + m.size, m.x..m.x + m.size),
+            None => (0..0, 0..0),
+        };
+        let mut out = String::new();
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                if rows.contains(&y) && columns.contains(&x) {
+                    out.push('X');
+                } else {
+                    let level = (self.power_cell(x, y).0 + 5).clamp(0, 9);
+                    out.push(RAMP[level as usize] as char);
+                }
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+
+    fn squares(&self, size: usize) -> impl Iterator<Item = Square> + '_ {
+        (0..=self.height - size)
+            .flat_map(move |r| (0..=self.width - size).map(move |c| (r, c)))
+            .map(move |(r, c)| {
+                let power = self.sum(c + size, r + size) + self.sum(c, r)
+                    - self.sum(c, r + size)
+                    - self.sum(c + size, r);
+                Square {
+                    power,
+                    x: c + 1,
+                    y: r + 1,
+                    size,
+                }
+            })
+    }
+
+    // The `k` best squares of side `size`, best first.
+    pub fn best_windows(&self, size: usize, k: usize) -> Vec<Square> {
+        top_k(self.squares(size), k)
+    }
+
+    // The `k` best squares of any size that fits in the grid.
+    pub fn best_windows_any_size(&self, k: usize) -> Vec<Square> {
+        let sizes = 1..=self.width.min(self.height);
+        top_k(sizes.flat_map(|size| self.best_windows(size, k)), k)
+    }
+
+    // Like `best_windows_any_size`, with the sizes dealt out round-robin to `threads` threads so
+    // each gets a share of the small sizes, which have the most squares.
+    pub fn best_windows_any_size_parallel(&self, k: usize, threads: usize) -> Vec<Square> {
+        let sizes = self.width.min(self.height);
+        let threads = threads.clamp(1, sizes);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (1..=threads)
+                .map(|first| {
+                    scope.spawn(move || {
+                        let sizes = (first..=sizes).step_by(threads);
+                        top_k(sizes.flat_map(|size| self.best_windows(size, k)), k)
+                    })
+                })
+                .collect();
+            let best = workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap());
+            top_k(best, k)
+        })
+    }
+}
+
+// The best 3x3 square and the best square of any size.
+pub struct Answer {
+    pub part1: Square,
+    pub part2: Square,
+}
+
+impl Answer {
+    pub fn to_json(&self) -> String {
+        let (p1, p2) = (&self.part1, &self.part2);
+        format!(
+            "{{\"part1\":{{\"x\":{},\"y\":{},\"power\":{}}},\
+             \"part2\":{{\"x\":{},\"y\":{},\"size\":{},\"power\":{}}}}}",
+            p1.x, p1.y, p1.power, p2.x, p2.y, p2.size, p2.power
+        )
+    }
+}
+
+pub fn solve(grid: &Grid) -> Answer {
+    solve_with(grid, 1)
+}
+
+// Like `solve`, but searches the sizes for part 2 on `threads` threads when there's more than one.
+pub fn solve_with(grid: &Grid, threads: usize) -> Answer {
+    let part2 = if threads > 1 {
+        grid.best_windows_any_size_parallel(1, threads)
+    } else {
+        grid.best_windows_any_size(1)
+    };
+    Answer {
+        part1: grid.best_windows(3, 1)[0],
+        part2: part2[0],
+    }
+}
+
+#[test]
+fn test_solve() {
+    let answer = solve(&Grid::from_serial_num(18, 300, 300));
+    assert_eq!(
+        answer.to_json(),
+        r#"{"part1":{"x":33,"y":45,"power":29},"part2":{"x":90,"y":269,"size":16,"power":113}}"#
+    );
+    let grid = Grid::from_serial_num(42, 60, 60);
+    assert_eq!(solve_with(&grid, 3).to_json(), solve(&grid).to_json());
+}
+
+// Finds the same squares as `Grid::best_windows` without the whole table, building the sums a
+// row at a time and keeping only the last `size + 1` rows of them.
+pub fn best_windows_banded(
+    serial_num: i32,
+    width: usize,
+    height: usize,
+    size: usize,
+    k: usize,
+) -> Vec<Square> {
+    let mut rows = vec![vec![0i64; width + 1]; size + 1];
+    let mut best = Vec::new();
+    for y in 1..=height {
+        let (prev, cur) = ((y - 1) % (size + 1), y % (size + 1));
+        let mut row_sum = 0;
+        let mut row = mem::take(&mut rows[cur]);
+        for (x, (sum, above)) in row.iter_mut().zip(&rows[prev]).enumerate().skip(1) {
+            row_sum += i64::from(PowerCell::generate(x, y, serial_num).0);
+            *sum = above + row_sum;
+        }
+        rows[cur] = row;
+        if y >= size {
+            let (top, bottom) = (&rows[(y - size) % (size + 1)], &rows[cur]);
+            let squares = (0..=width - size).map(|c| Square {
+                power: bottom[c + size] - bottom[c] - top[c + size] + top[c],
+                x: c + 1,
+                y: y - size + 1,
+                size,
+            });
+            best = top_k(best.into_iter().chain(squares), k);
+        }
+    }
+    best
+}
+
+#[test]
+fn test_best_windows_banded() {
+    let grid = Grid::from_serial_num(18, 300, 300);
+    for &size in &[3, 16] {
+        assert_eq!(
+            best_windows_banded(18, 300, 300, size, 3),
+            grid.best_windows(size, 3)
+        );
+    }
+    let grid = Grid::from_serial_num(42, 40, 25);
+    for size in 1..=25 {
+        assert_eq!(
+            best_windows_banded(42, 40, 25, size, 2),
+            grid.best_windows(size, 2)
+        );
+    }
+}
+
+#[test]
+fn test_large_sums() {
+    // Cells this powerful wrap an i32 sum within a couple of rows, as would a few hundred million
+    // ordinary ones.
+    let power = 1 << 30;
+    let grid = Grid::from_powers(3, 3, |_, _| power);
+    assert_eq!(grid.power_cell(3, 3).0, power);
+    let best = grid.best_windows_any_size(1)[0];
+    assert_eq!(
+        best,
+        Square {
+            power: 9 * i64::from(power),
+            x: 1,
+            y: 1,
+            size: 3
+        }
+    );
+}
+
+#[test]
+fn test_power_cell() {
+    assert_eq!(PowerCell::generate(3, 5, 8).0, 4);
+    assert_eq!(PowerCell::generate(122, 79, 57).0, -5);
+    assert_eq!(PowerCell::generate(217, 196, 39).0, 0);
+    assert_eq!(PowerCell::generate(101, 153, 71).0, 4);
+    // Far enough out that the product overflows i32.
+    assert_eq!(PowerCell::generate(2000, 2000, 18).0, -4);
+    assert_eq!(PowerCell::generate(1500, 1501, 7803).0, 1);
+    assert_eq!(PowerCell::generate(30000, 30000, 42).0, -1);
+}
+
+#[test]
+fn test_heatmap() -> Result<()> {
+    // Power runs -5 to 4 along the first row, and the rest is a plateau at 4 around a pit at -5.
+    let grid = Grid::from_powers(10, 4, |x, y| match (x, y) {
+        (x, 1) => x as i32 - 6,
+        (5, 3) => -5,
+        _ => 4,
+    });
+    assert_eq!(grid.power_cell(7, 1).0, 1);
+    let best = grid.best_windows(3, 1)[0];
+    assert_eq!((best.x, best.y), (1, 2));
+    let region = Region {
+        x: 1,
+        y: 1,
+        width: 10,
+        height: 4,
+    };
+    assert_eq!(
+        grid.heatmap(&region, Some(&best))?,
+        " .:-=+*#%@\nXXX@@@@@@@\nXXX@ @@@@@\nXXX@@@@@@@\n"
+    );
+    // Nothing is marked when the window is outside the region, and only the overlap when it's
+    // partly inside.
+    let region: Region = "4,2,3,2".parse()?;
+    assert_eq!(grid.heatmap(&region, None)?, "@@@\n@ @\n");
+    assert_eq!(grid.heatmap(&region, Some(&best))?, "@@@\n@ @\n");
+    let region: Region = "2,1,3,2".parse()?;
+    assert_eq!(grid.heatmap(&region, Some(&best))?, ".:-\nXX@\n");
+
+    let err = grid.heatmap(&"9,1,3,1".parse()?, None).unwrap_err();
+    assert_eq!(err.to_string(), "region 9,1,3,1 is outside the grid");
+    assert!("1,2,3".parse::<Region>().is_err());
+    assert!("1,2,0,4".parse::<Region>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_best_windows() {
+    let grid = Grid::from_serial_num(18, 300, 300);
+    let best: Vec<_> = grid
+        .best_windows(3, 3)
+        .iter()
+        .map(|s| (s.power, s.x, s.y))
+        .collect();
+    assert_eq!(best, [(29, 33, 45), (28, 21, 92), (28, 235, 59)]);
+    let best: Vec<_> = grid
+        .best_windows_any_size(3)
+        .iter()
+        .map(|s| (s.power, s.x, s.y, s.size))
+        .collect();
+    assert_eq!(
+        best,
+        [(113, 90, 269, 16), (106, 90, 269, 15), (101, 90, 270, 15)]
+    );
+
+    let square = Grid::from_serial_num(42, 300, 300).best_windows(3, 1)[0];
+    assert_eq!(
+        square,
+        Square {
+            power: 30,
+            x: 21,
+            y: 61,
+            size: 3
+        }
+    );
+
+    // (2, 1) and (1, 2) tie on their own and the 2x2 square around (1, 1) ties with them too.
+    // The 1x1 squares win for being smaller, and (1, 2) for having the smaller x.
+    let grid = Grid::from_powers(3, 3, |x, y| match (x, y) {
+        (2, 1) | (1, 2) => 4,
+        (1, 1) => -1,
+        (2, 2) => -3,
+        _ => -5,
+    });
+    assert_eq!(
+        grid.best_windows(2, 1),
+        [Square {
+            power: 4,
+            x: 1,
+            y: 1,
+            size: 2
+        }]
+    );
+    assert_eq!(
+        grid.best_windows_any_size(3),
+        [
+            Square {
+                power: 4,
+                x: 1,
+                y: 2,
+                size: 1
+            },
+            Square {
+                power: 4,
+                x: 2,
+                y: 1,
+                size: 1
+            },
+            Square {
+                power: 4,
+                x: 1,
+                y: 1,
+                size: 2
+            },
+        ]
+    );
+
+    let flat = Grid::from_powers(4, 4, |_, _| 0);
+    assert_eq!(
+        flat.best_windows_any_size(1),
+        [Square {
+            power: 0,
+            x: 1,
+            y: 1,
+            size: 1
+        }]
+    );
+}
+
+#[test]
+fn test_max_any_size_parallel() {
+    let grid = Grid::from_serial_num(42, 300, 300);
+    let serial = grid.best_windows_any_size(3);
+    assert_eq!(
+        serial[0],
+        Square {
+            power: 119,
+            x: 232,
+            y: 251,
+            size: 12
+        }
+    );
+    for &threads in &[3, 1000] {
+        let parallel = grid.best_windows_any_size_parallel(3, threads);
+        assert_eq!(parallel, serial, "{} threads", threads);
+    }
+}
+
+#[test]
+fn test_small_grid() {
+    // Every square on a small, non-square grid against adding up its cells directly.
+    let (width, height) = (10, 7);
+    let grid = Grid::from_serial_num(18, width, height);
+    for size in 1..=height {
+        let mut expected = (i64::MIN, 1, 1);
+        for x in 1..=width - size + 1 {
+            for y in 1..=height - size + 1 {
+                let power = (y..y + size)
+                    .flat_map(|cy| (x..x + size).map(move |cx| PowerCell::generate(cx, cy, 18).0))
+                    .map(i64::from)
+                    .sum();
+                if power > expected.0 {
+                    expected = (power, x, y);
+                }
+            }
+        }
+        let square = grid.best_windows(size, 1)[0];
+        assert_eq!(
+            (square.power, square.x, square.y),
+            expected,
+            "size {}",
+            size
+        );
+    }
+}
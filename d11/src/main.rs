@@ -1,81 +1,82 @@
+use d11::{best_windows_banded, solve_with, Grid, Region, Result, DEFAULT_HEIGHT, DEFAULT_WIDTH};
 use std::env;
-use std::error::Error;
-use std::i32;
-use std::result;
+use std::thread;
 
-type Result<T> = result::Result<T, Box<Error>>;
-
-const WIDTH: usize = 300;
-const HEIGHT: usize = 300;
-
-#[derive(Clone, Copy)]
-struct PowerCell(i32);
-
-impl PowerCell {
-    fn generate(x: usize, y: usize, serial_num: i32) -> Self {
-        let (x, y) = (x as i32, y as i32);
-        let rack_id = x + 10;
-        let mut result = rack_id * y;
-        result += serial_num;
-        result *= rack_id;
-        let result = (result / 100) % 10;
-        Self(result - 5)
+fn main() -> Result<()> {
+    let mut serial_num = None;
+    let (mut width, mut height) = (DEFAULT_WIDTH, DEFAULT_HEIGHT);
+    let mut threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut top = 1;
+    let mut heatmap = false;
+    let mut banded = false;
+    let mut json = false;
+    let mut region = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" => width = args.next().ok_or("missing width")?.parse()?,
+            "--height" => height = args.next().ok_or("missing height")?.parse()?,
+            "--threads" => threads = args.next().ok_or("missing thread count")?.parse()?,
+            "--top" => top = args.next().ok_or("missing window count")?.parse()?,
+            "--heatmap" => heatmap = true,
+            "--banded" => banded = true,
+            "--json" => json = true,
+            "--region" => region = Some(args.next().ok_or("missing region")?.parse()?),
+            _ if serial_num.is_none() => serial_num = Some(arg.parse()?),
+            _ => return Err(format!("unexpected argument {}", arg).into()),
+        }
+    }
+    let serial_num: i32 = serial_num.ok_or("missing serial_num")?;
+    if width < 3 || height < 3 {
+        return Err("grid must be at least 3x3".into());
+    }
+    if top == 0 {
+        return Err("--top needs at least 1 window".into());
     }
-}
-
-struct Grid {
-    sums: [[i32; WIDTH + 1]; HEIGHT + 1],
-}
 
-impl Grid {
-    fn from_serial_num(serial_num: i32) -> Self {
-        let mut power_cells = [[PowerCell(0); WIDTH + 1]; HEIGHT + 1];
-        for y in 1..=HEIGHT {
-            for x in 1..=WIDTH {
-                power_cells[y][x] = PowerCell::generate(x, y, serial_num);
-            }
+    // Grids too big for a full table of sums can still answer part 1 a band of rows at a time.
+    if banded {
+        if json {
+            return Err("--json needs part 2, which --banded skips".into());
         }
-
-        let mut sums = [[0; WIDTH + 1]; HEIGHT + 1];
-        for y in 1..=HEIGHT {
-            for x in 1..=WIDTH {
-                sums[y][x] =
-                    sums[y][x - 1] + sums[y - 1][x] - sums[y - 1][x - 1] + power_cells[y][x].0;
-            }
+        for square in best_windows_banded(serial_num, width, height, 3, top) {
+            println!("{},{} (power {})", square.x, square.y, square.power);
         }
-
-        Self { sums }
+        return Ok(());
     }
 
-    fn max(&self, size: usize) -> (i32, usize, usize) {
-        let mut result = (i32::MIN, 1, 1);
-        for r in 0..=HEIGHT - size {
-            for c in 0..=WIDTH - size {
-                let power = self.sums[r + size][c + size] + self.sums[r][c]
-                    - self.sums[r + size][c]
-                    - self.sums[r][c + size];
-                if power > result.0 {
-                    result = (power, c + 1, r + 1);
-                }
-            }
-        }
-        result
+    let grid = Grid::from_serial_num(serial_num, width, height);
+    if heatmap {
+        let region = region.unwrap_or(Region {
+            x: 1,
+            y: 1,
+            width,
+            height,
+        });
+        let best = grid.best_windows(3, 1)[0];
+        print!("{}", grid.heatmap(&region, Some(&best))?);
     }
-}
 
-fn main() -> Result<()> {
-    let mut args = env::args();
-    args.next();
-    let serial_num: i32 = args.next().ok_or("missing serial_num")?.parse()?;
-    if args.next() != None {
-        return Err("expected 1 argument".into());
+    // JSON only has room for the best square of each part, as `solve` finds them.
+    if json {
+        println!("{}", solve_with(&grid, threads).to_json());
+        return Ok(());
     }
+    let part1 = grid.best_windows(3, top);
+    let part2 = if threads > 1 {
+        grid.best_windows_any_size_parallel(top, threads)
+    } else {
+        grid.best_windows_any_size(top)
+    };
 
-    let grid = Grid::from_serial_num(serial_num);
-    let (_, x, y) = grid.max(3);
-    println!("{},{}", x, y);
-
-    let ((_, x, y), size) = (1..WIDTH).map(|size| (grid.max(size), size)).max().unwrap();
-    println!("{},{},{}", x, y, size);
+    for square in part1 {
+        println!("{},{} (power {})", square.x, square.y, square.power);
+    }
+    for square in part2 {
+        println!(
+            "{},{},{} (power {})",
+            square.x, square.y, square.size, square.power
+        );
+    }
     Ok(())
 }
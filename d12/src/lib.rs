@@ -0,0 +1,922 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::result;
+use std::str::FromStr;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+type Transitions = BTreeMap<Vec<u8>, u8>;
+
+#[derive(Clone)]
+pub struct Rules {
+    transitions: Transitions,
+}
+
+fn all_patterns() -> impl Iterator<Item = Vec<u8>> {
+    (0..32).map(|n| {
+        (0..5)
+            .map(|bit| if n & (16 >> bit) != 0 { b'#' } else { b'.' })
+            .collect()
+    })
+}
+
+impl Rules {
+    fn missing(&self) -> Vec<Vec<u8>> {
+        all_patterns()
+            .filter(|p| !self.transitions.contains_key(p))
+            .collect()
+    }
+
+    // The next state of each window's middle pot as 0 or 1, indexed by reading the window as
+    // bits with the leftmost pot highest.
+    fn table(&self) -> [u8; 32] {
+        let mut table = [0; 32];
+        for (n, pattern) in all_patterns().enumerate() {
+            table[n] = (self.get(&pattern) == b'#') as u8;
+        }
+        table
+    }
+
+    // Patterns without a rule evolve to '.'.
+    fn get(&self, window: &[u8]) -> u8 {
+        self.transitions.get(window).copied().unwrap_or(b'.')
+    }
+}
+
+fn is_pot(c: u8) -> bool {
+    c == b'.' || c == b'#'
+}
+
+fn parse_transition(line: &str) -> Result<(Vec<u8>, u8)> {
+    let malformed = || format!("expected transition like `..#.# => #`, got `{}`", line);
+    let mut parts = line.split("=>");
+    let (from, to) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(from), Some(to), None) => (from.trim().as_bytes(), to.trim().as_bytes()),
+        _ => return Err(malformed().into()),
+    };
+    if !from.iter().all(|&c| is_pot(c)) || to.len() != 1 || !is_pot(to[0]) {
+        return Err(malformed().into());
+    }
+    if from.len() != 5 {
+        let from = String::from_utf8_lossy(from);
+        return Err(format!("pattern `{}` has {} pots, expected 5", from, from.len()).into());
+    }
+    Ok((from.to_vec(), to[0]))
+}
+
+// Repeating a transition is fine, but not giving the same pattern two different outcomes.
+fn insert_transition(transitions: &mut Transitions, line: &str) -> Result<()> {
+    let (from, to) = parse_transition(line)?;
+    match transitions.insert(from.clone(), to) {
+        Some(previous) if previous != to => Err(format!(
+            "conflicting transitions for `{}`: `{}` and `{}`",
+            String::from_utf8_lossy(&from),
+            previous as char,
+            to as char
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+fn parse_header(line: &str) -> Result<Vec<u8>> {
+    let initial = line
+        .trim()
+        .strip_prefix("initial state:")
+        .ok_or_else(|| format!("expected `initial state: ...`, got `{}`", line.trim()))?;
+    parse_state(initial).map_err(|_| format!("malformed initial state `{}`", line.trim()).into())
+}
+
+fn parse_state(state: &str) -> Result<Vec<u8>> {
+    let state = state.trim().as_bytes();
+    if !state.iter().all(|&c| is_pot(c)) {
+        return Err(format!(
+            "malformed initial state `{}`",
+            String::from_utf8_lossy(state)
+        )
+        .into());
+    }
+    Ok(state.to_vec())
+}
+
+// Reads the initial state line, if `header` is set, and then the rules, skipping blank lines.
+fn read(r: impl BufRead, header: bool) -> Result<(Option<Vec<u8>>, Rules)> {
+    let mut initial = None;
+    let mut transitions = BTreeMap::new();
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let at_line = |e: Box<dyn Error>| format!("line {}: {}", i + 1, e);
+        if header && initial.is_none() {
+            initial = Some(parse_header(&line).map_err(at_line)?);
+        } else {
+            insert_transition(&mut transitions, &line).map_err(at_line)?;
+        }
+    }
+    Ok((initial, Rules { transitions }))
+}
+
+fn parse(r: impl BufRead) -> Result<(Vec<u8>, Rules)> {
+    let (initial, rules) = read(r, true)?;
+    Ok((initial.ok_or("empty input")?, rules))
+}
+
+// Rules on their own, one transition a line, as in a `--rules` file.
+impl FromStr for Rules {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(read(s.as_bytes(), false)?.1)
+    }
+}
+
+// The initial state and rules of a whole input held in a string.
+pub fn parse_str(s: &str) -> Result<(Vec<u8>, Rules)> {
+    parse(s.as_bytes())
+}
+
+pub fn parse_input(r: impl BufRead) -> Result<Pots> {
+    let (initial, rules) = parse(r)?;
+    Ok(Pots::new(initial, rules))
+}
+
+// Like `parse_input`, but with the initial state or the rules, or both, given separately. `r` is
+// only read for whichever isn't, and then holds the usual combined input.
+pub fn parse_input_with(
+    r: impl BufRead,
+    state: Option<&str>,
+    rules: Option<impl BufRead>,
+) -> Result<Pots> {
+    let read_rules = |r| match read(r, false) {
+        Ok((_, rules)) => Ok(rules),
+        Err(e) => Err(format!("rules: {}", e)),
+    };
+    let (initial, rules) = match (state, rules) {
+        (Some(state), Some(rules)) => (parse_state(state)?, read_rules(rules)?),
+        (state, rules) => {
+            let (initial, combined) = parse(r)?;
+            let initial = match state {
+                Some(state) => parse_state(state)?,
+                None => initial,
+            };
+            let rules = match rules {
+                Some(rules) => read_rules(rules)?,
+                None => combined,
+            };
+            (initial, rules)
+        }
+    };
+    Ok(Pots::new(initial, rules))
+}
+
+/// How many pots the live ones may span before `Pots::evolve` gives up, by default.
+pub const DEFAULT_MAX_WIDTH: u64 = 10_000_000;
+
+// Pots packed 64 to a word, lowest bit first, with one empty word kept before and after the live
+// pots so plants can spread into them. Plants spread at most two pots a generation, so keeping
+// that margin only ever pushes or pops a word at either end rather than copying the row. 100k
+// generations of the input take about 0.1s in a release build; a byte per pot with a map lookup
+// per window took 1.6s for 10k, growing quadratically as the plants drifted right.
+#[derive(Clone)]
+pub struct Pots {
+    words: VecDeque<u64>,
+    table: [u8; 32],
+    // The number of the pot in the lowest bit of the first word.
+    start: i64,
+    // The patterns the input had no rule for.
+    missing: Vec<Vec<u8>>,
+    generation: u64,
+    max_width: u64,
+}
+
+impl Pots {
+    pub fn new(state: Vec<u8>, rules: Rules) -> Self {
+        let mut words = VecDeque::from(vec![0; state.len() / 64 + 1]);
+        for (i, &c) in state.iter().enumerate() {
+            if c == b'#' {
+                words[i / 64] |= 1 << (i % 64);
+            }
+        }
+        let mut pots = Self {
+            words,
+            table: rules.table(),
+            start: 0,
+            missing: rules.missing(),
+            generation: 0,
+            max_width: DEFAULT_MAX_WIDTH,
+        };
+        pots.pad();
+        pots
+    }
+
+    pub fn set_max_width(&mut self, max_width: u64) {
+        self.max_width = max_width;
+    }
+
+    pub fn missing_rules(&self) -> &[Vec<u8>] {
+        &self.missing
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self.missing.first() {
+            Some(missing) => {
+                Err(format!("no transition for `{}`", String::from_utf8_lossy(missing)).into())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Trims or extends either end to leave exactly one empty word there, or a single empty word if
+    // no plants are left.
+    fn pad(&mut self) {
+        while self.words.len() > 1 && self.words[0] == 0 && self.words[1] == 0 {
+            self.words.pop_front();
+            self.start += 64;
+        }
+        while self.words.len() > 1 && self.words.iter().rev().take(2).all(|&w| w == 0) {
+            self.words.pop_back();
+        }
+        if self.words.front() != Some(&0) {
+            self.words.push_front(0);
+            self.start -= 64;
+        }
+        if self.words.back() != Some(&0) {
+            self.words.push_back(0);
+        }
+    }
+
+    // Fails once the live pots span more than the maximum width, as they do forever with rules
+    // like `..... => #`.
+    pub fn evolve(&mut self) -> Result<()> {
+        let mut next = vec![0; self.words.len()];
+        let mut window = 0;
+        for i in 0..self.words.len() * 64 {
+            window = (window << 1 | (self.words[i / 64] >> (i % 64) & 1) as usize) & 31;
+            // The window ends at pot i, so it's centered on pot i - 2.
+            if i >= 2 && self.table[window] == 1 {
+                next[(i - 2) / 64] |= 1 << ((i - 2) % 64);
+            }
+        }
+        self.words = next.into();
+        self.pad();
+        self.generation += 1;
+        match self.extent() {
+            Some((first, last)) if (last - first + 1) as u64 > self.max_width => Err(format!(
+                "live pots span {}..={} at generation {}, wider than the limit of {} pots",
+                first, last, self.generation, self.max_width
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    // The numbers of the leftmost and rightmost live pots.
+    pub fn extent(&self) -> Option<(i64, i64)> {
+        let first = self.words.iter().position(|&w| w != 0)?;
+        let last = self.words.iter().rposition(|&w| w != 0)?;
+        let pot = |word: usize, bit: u32| self.start + (word * 64) as i64 + bit as i64;
+        Some((
+            pot(first, self.words[first].trailing_zeros()),
+            pot(last, 63 - self.words[last].leading_zeros()),
+        ))
+    }
+
+    fn is_live(&self, pot: i64) -> bool {
+        let i = (pot - self.start) as usize;
+        pot >= self.start && i < self.words.len() * 64 && self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    fn row(&self, first: i64, last: i64) -> String {
+        (first..=last)
+            .map(|i| if self.is_live(i) { '#' } else { '.' })
+            .collect()
+    }
+
+    fn live(&self) -> impl Iterator<Item = i64> + '_ {
+        self.words.iter().enumerate().flat_map(move |(w, &word)| {
+            (0..64)
+                .filter(move |bit| word >> bit & 1 == 1)
+                .map(move |bit| self.start + (w * 64 + bit) as i64)
+        })
+    }
+
+    // The live pots shifted so the first is in the lowest bit, to compare shapes wherever they are.
+    fn trimmed(&self) -> Vec<u64> {
+        let first = match self.live().next() {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+        let mut words = Vec::new();
+        for pot in self.live() {
+            let i = (pot - first) as usize;
+            if i / 64 >= words.len() {
+                words.resize(i / 64 + 1, 0);
+            }
+            words[i / 64] |= 1 << (i % 64);
+        }
+        words
+    }
+
+    pub fn sum(&self) -> i64 {
+        self.live().sum()
+    }
+
+    pub fn live_pots(&self) -> u64 {
+        self.words.iter().map(|w| u64::from(w.count_ones())).sum()
+    }
+}
+
+// Prints the live region prefixed by the index of its leftmost pot, under a ruler marking every
+// tenth pot with its tens digit.
+impl fmt::Display for Pots {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (first, last) = match self.extent() {
+            Some(extent) => extent,
+            None => return writeln!(f, "(no live pots)"),
+        };
+        let prefix = format!("{}: ", first);
+        writeln!(
+            f,
+            "{:width$}{}",
+            "",
+            ruler(first, last),
+            width = prefix.len()
+        )?;
+        writeln!(f, "{}{}", prefix, self.row(first, last))
+    }
+}
+
+// Marks every tenth pot from `first` to `last` with its tens digit.
+fn ruler(first: i64, last: i64) -> String {
+    let ruler: String = (first..=last)
+        .map(|i| match i {
+            0 => '0',
+            i if i % 10 == 0 => (b'0' + ((i / 10).abs() % 10) as u8) as char,
+            _ => ' ',
+        })
+        .collect();
+    ruler.trim_end().to_string()
+}
+
+// Renders generations 0 through `generations`, one numbered line each. Every line covers the same
+// pots, from the leftmost plant or pot 0 to the rightmost, so pot 0 stays under the ruler's `0`.
+pub fn trace(mut pots: Pots, generations: u64) -> Result<String> {
+    let mut states = vec![pots.clone()];
+    for _ in 0..generations {
+        pots.evolve()?;
+        states.push(pots.clone());
+    }
+    let extents = states.iter().filter_map(Pots::extent);
+    let first = extents
+        .clone()
+        .map(|(first, _)| first)
+        .min()
+        .unwrap_or(0)
+        .min(0);
+    let last = extents.map(|(_, last)| last).max().unwrap_or(0).max(0);
+    let width = generations.to_string().len();
+    let mut trace = format!("{:width$}  {}\n", "", ruler(first, last), width = width);
+    for (generation, pots) in states.iter().enumerate() {
+        trace += &format!(
+            "{:>width$}: {}\n",
+            generation,
+            pots.row(first, last),
+            width = width
+        );
+    }
+    Ok(trace)
+}
+
+/// Alternative to `Pots` that only tracks the indices of live pots, so negative indices need no
+/// padding and memory scales with the number of plants rather than the span they cover. Slower
+/// than `Pots` once the plants are dense, but simple enough to check it against.
+pub struct SparsePots {
+    live: BTreeSet<i64>,
+    rules: Rules,
+}
+
+impl SparsePots {
+    pub fn new(state: &[u8], rules: Rules) -> Self {
+        let live = state
+            .iter()
+            .enumerate()
+            .filter(|&(_, &c)| c == b'#')
+            .map(|(i, _)| i as i64)
+            .collect();
+        Self { live, rules }
+    }
+
+    fn window(&self, center: i64) -> Vec<u8> {
+        (center - 2..=center + 2)
+            .map(|i| if self.live.contains(&i) { b'#' } else { b'.' })
+            .collect()
+    }
+
+    pub fn evolve(&mut self) -> Result<()> {
+        let candidates: BTreeSet<i64> = self.live.iter().flat_map(|&i| i - 2..=i + 2).collect();
+        let mut next = BTreeSet::new();
+        for i in candidates {
+            if self.rules.get(&self.window(i)) == b'#' {
+                next.insert(i);
+            }
+        }
+        self.live = next;
+        Ok(())
+    }
+
+    pub fn sum(&self) -> i64 {
+        self.live.iter().sum()
+    }
+}
+
+// The generation from which the pattern repeats its shape every `period` generations, moving
+// along and changing the sum by `delta` each period.
+#[derive(Debug, PartialEq)]
+pub struct Steady {
+    pub generation: u64,
+    pub period: u64,
+    pub delta: i64,
+}
+
+// How many recent generations to look for a repeated shape among.
+const HISTORY: usize = 1000;
+
+pub fn project(pots: Pots, generations: u64) -> Result<(i64, Option<Steady>)> {
+    project_with(pots, generations, |_, _| {})
+}
+
+// The sum after `generations`, projected from the first repeat of a recent generation's shape.
+// Calls `each` with every generation actually simulated.
+pub fn project_with(
+    mut pots: Pots,
+    generations: u64,
+    mut each: impl FnMut(u64, &Pots),
+) -> Result<(i64, Option<Steady>)> {
+    // The shape and sum of each recent generation, from `first` on, and where each shape was seen.
+    let mut history: VecDeque<(Vec<u64>, i64)> = VecDeque::new();
+    let mut seen: HashMap<Vec<u64>, u64> = HashMap::new();
+    let mut first = 0;
+    for generation in 0..=generations {
+        if generation > 0 {
+            pots.evolve()?;
+        }
+        each(generation, &pots);
+        let (shape, sum) = (pots.trimmed(), pots.sum());
+        if let Some(&start) = seen.get(&shape) {
+            let period = generation - start;
+            let remaining = generations - generation;
+            // Finish the partial period first, so the rest are whole periods from the same phase
+            // as a recorded generation.
+            let leftover = remaining % period;
+            for i in 1..=leftover {
+                pots.evolve()?;
+                each(generation + i, &pots);
+            }
+            let phase_delta = pots.sum() - history[(start + leftover - first) as usize].1;
+            let projection = pots.sum() + phase_delta * (remaining / period) as i64;
+            let delta = sum - history[(start - first) as usize].1;
+            return Ok((
+                projection,
+                Some(Steady {
+                    generation: start,
+                    period,
+                    delta,
+                }),
+            ));
+        }
+        if generation == generations {
+            return Ok((sum, None));
+        }
+        seen.insert(shape.clone(), generation);
+        history.push_back((shape, sum));
+        if history.len() > HISTORY {
+            if let Some((shape, _)) = history.pop_front() {
+                seen.remove(&shape);
+            }
+            first += 1;
+        }
+    }
+    unreachable!()
+}
+
+pub fn sum_after(pots: Pots, generations: u64) -> Result<i64> {
+    Ok(project(pots, generations)?.0)
+}
+
+// Writes `generation,sum,live_pots` for each generation simulated on the way to `generations`,
+// which stops short once the pattern is found to repeat.
+pub fn write_csv(pots: Pots, generations: u64, out: &mut impl Write) -> Result<()> {
+    writeln!(out, "generation,sum,live_pots")?;
+    let mut written = Ok(());
+    project_with(pots, generations, |generation, pots| {
+        if written.is_ok() {
+            written = writeln!(out, "{},{},{}", generation, pots.sum(), pots.live_pots());
+        }
+    })?;
+    Ok(written?)
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "initial state: #..#.#..##......###...###
+
+...## => #
+..#.. => #
+.#... => #
+.#.#. => #
+.#.## => #
+.##.. => #
+.#### => #
+#.#.# => #
+#.### => #
+##.#. => #
+##.## => #
+###.. => #
+###.# => #
+####. => #
+";
+
+/// The example only lists the rules producing a plant, so fill in the rest with '.'.
+#[cfg(test)]
+fn example() -> Result<(Vec<u8>, Rules)> {
+    let (initial, mut rules) = parse(EXAMPLE.as_bytes())?;
+    for pattern in all_patterns() {
+        rules.transitions.entry(pattern).or_insert(b'.');
+    }
+    Ok((initial, rules))
+}
+
+#[test]
+fn test_example() -> Result<()> {
+    assert_eq!(sum_after(parse_input(EXAMPLE.as_bytes())?, 20)?, 325);
+    let (initial, rules) = parse_str(EXAMPLE)?;
+    assert_eq!(sum_after(Pots::new(initial, rules), 20)?, 325);
+    Ok(())
+}
+
+#[test]
+fn test_sparse_matches_packed() -> Result<()> {
+    for (initial, rules) in [parse(EXAMPLE.as_bytes())?, example()?] {
+        let mut sparse = SparsePots::new(&initial, rules.clone());
+        let mut packed = Pots::new(initial, rules);
+        for generation in 1..=200 {
+            sparse.evolve()?;
+            packed.evolve()?;
+            assert_eq!(
+                sparse.live,
+                packed.live().collect(),
+                "generation {}",
+                generation
+            );
+            assert_eq!(sparse.sum(), packed.sum());
+            if generation == 20 {
+                assert_eq!(packed.sum(), 325);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_sparse_matches_packed_random_rules() -> Result<()> {
+    let mut seed = 1u64;
+    let mut next = |n: u64| {
+        seed = (seed * 1103515245 + 12345) % (1 << 31);
+        (seed >> 8) % n
+    };
+    for _ in 0..50 {
+        let initial: Vec<u8> = (0..next(100) + 1)
+            .map(|_| b".#"[next(2) as usize])
+            .collect();
+        // An empty window must stay empty, or plants would fill the endless row on either side.
+        let transitions = all_patterns()
+            .map(|pattern| {
+                let to = if pattern == b"....." {
+                    b'.'
+                } else {
+                    b".#"[next(2) as usize]
+                };
+                (pattern, to)
+            })
+            .collect();
+        let rules = Rules { transitions };
+        let mut sparse = SparsePots::new(&initial, rules.clone());
+        let mut packed = Pots::new(initial, rules);
+        for generation in 1..=100 {
+            sparse.evolve()?;
+            packed.evolve()?;
+            assert_eq!(
+                sparse.live,
+                packed.live().collect(),
+                "generation {}",
+                generation
+            );
+            assert_eq!(sparse.sum(), packed.sum());
+            assert!(packed.words.len() >= 2 || packed.extent().is_none());
+            assert_eq!(
+                (packed.words[0], packed.words[packed.words.len() - 1]),
+                (0, 0)
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_sum_after() -> Result<()> {
+    let (initial, rules) = example()?;
+    let pots = Pots::new(initial, rules);
+    assert_eq!(project(pots.clone(), 20)?, (325, None));
+
+    let steady = Some(Steady {
+        generation: 86,
+        period: 1,
+        delta: 20,
+    });
+    assert_eq!(project(pots.clone(), 1000)?, (19374, steady));
+    let mut simulated = pots.clone();
+    for _ in 0..1000 {
+        simulated.evolve()?;
+    }
+    assert_eq!(simulated.sum(), 19374);
+    assert_eq!(sum_after(pots, 50_000_000_000)?, 999_999_999_374);
+    Ok(())
+}
+
+#[test]
+fn test_sum_after_period_2() -> Result<()> {
+    // A lone plant becomes two just to its right, which become one two pots past those.
+    let pots = parse_input(&b"initial state: #\n\n#.... => #\n.#... => #\n"[..])?;
+    let steady = Steady {
+        generation: 0,
+        period: 2,
+        delta: 4,
+    };
+    for &generations in &[0, 1, 2, 7, 1000, 1001] {
+        let mut simulated = pots.clone();
+        for _ in 0..generations {
+            simulated.evolve()?;
+        }
+        let (sum, found) = project(pots.clone(), generations)?;
+        assert_eq!(sum, simulated.sum(), "generation {}", generations);
+        assert_eq!(
+            found.as_ref(),
+            if generations < 2 { None } else { Some(&steady) }
+        );
+    }
+    assert_eq!(sum_after(pots.clone(), 1001)?, 4003);
+    assert_eq!(sum_after(pots, 50_000_000_000)?, 100_000_000_000);
+    Ok(())
+}
+
+#[test]
+fn test_write_csv() -> Result<()> {
+    let mut out = Vec::new();
+    write_csv(parse_input(EXAMPLE.as_bytes())?, 4, &mut out)?;
+    // From the puzzle's diagram, where generation 1 has plants in pots 0, 4, 9, 15, 18, 21 and 24.
+    let expected = [
+        "generation,sum,live_pots",
+        "0,145,11",
+        "1,91,7",
+        "2,132,11",
+        "3,102,9",
+        "4,154,11",
+        "",
+    ];
+    assert_eq!(String::from_utf8(out)?, expected.join("\n"));
+
+    // From generation 86 the shape just moves right, so the rows stop once 87 repeats it.
+    let (initial, rules) = example()?;
+    let mut out = Vec::new();
+    write_csv(Pots::new(initial, rules), 50_000_000_000, &mut out)?;
+    let out = String::from_utf8(out)?;
+    assert_eq!(out.lines().count(), 89);
+    assert_eq!(out.lines().last(), Some("87,1114,20"));
+    Ok(())
+}
+
+#[test]
+fn test_trimmed() -> Result<()> {
+    let (_, rules) = example()?;
+    let shifted = |state: &str| Pots::new(state.as_bytes().to_vec(), rules.clone());
+    let pots = shifted("#..#.#");
+    assert_eq!(pots.trimmed(), vec![0b101001]);
+    assert_eq!(
+        shifted(&format!("{}#..#.#", ".".repeat(100))).trimmed(),
+        pots.trimmed()
+    );
+    assert_eq!(shifted(".....").trimmed(), Vec::<u64>::new());
+    let wide = shifted(&format!("#{}#", ".".repeat(70)));
+    assert_eq!(wide.trimmed(), vec![1, 1 << 7]);
+    let gap = shifted(&format!("#{}#", ".".repeat(200)));
+    assert_eq!(gap.trimmed(), vec![1, 0, 0, 1 << 9]);
+    Ok(())
+}
+
+#[test]
+fn test_missing_rules() -> Result<()> {
+    let mut pots = parse_input(EXAMPLE.as_bytes())?;
+    assert_eq!(pots.missing_rules().len(), 18);
+    assert_eq!(
+        pots.validate().unwrap_err().to_string(),
+        "no transition for `.....`"
+    );
+    for _ in 0..20 {
+        pots.evolve()?;
+    }
+    assert_eq!(pots.sum(), 325);
+
+    let (initial, complete) = example()?;
+    let mut pots = Pots::new(initial, complete);
+    assert!(pots.missing_rules().is_empty());
+    pots.validate()?;
+    for _ in 0..20 {
+        pots.evolve()?;
+    }
+    assert_eq!(pots.sum(), 325);
+    Ok(())
+}
+
+#[test]
+fn test_max_width() -> Result<()> {
+    // Every empty window sprouts a plant, so the row fills outwards without end.
+    let mut pots = parse_input(&b"initial state: #\n\n..... => #\n"[..])?;
+    pots.set_max_width(1000);
+    let err = sum_after(pots.clone(), 50_000_000_000)
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("live pots span -"), "{}", err);
+    assert!(
+        err.ends_with("wider than the limit of 1000 pots"),
+        "{}",
+        err
+    );
+
+    let mut extents = Vec::new();
+    let err = project_with(pots, 100, |generation, pots| {
+        extents.push((generation, pots.extent()))
+    });
+    assert!(err.is_err());
+    assert_eq!(extents[0], (0, Some((0, 0))));
+    let (generation, extent) = extents[extents.len() - 1];
+    let (first, last) = extent.unwrap();
+    assert!(last - first < 1000 && generation < 10);
+    Ok(())
+}
+
+#[test]
+fn test_trace() -> Result<()> {
+    let pots = parse_input(EXAMPLE.as_bytes())?;
+    let expected = "    0         1         2
+0: .#..#.#..##......###...###.
+1: .#...#....#.....#..#..#..#.
+2: .##..##...##....#..#..#..##
+3: #.#...#..#.#....#..#..#...#
+";
+    assert_eq!(trace(pots.clone(), 3)?, expected);
+    // Pot 0 stays in the ruler's column as the window widens.
+    let trace = trace(pots, 10)?;
+    let lines: Vec<_> = trace.lines().collect();
+    let zero = lines[0].find('0').unwrap();
+    assert!(lines[1].starts_with(" 0: ") && lines[1][zero..].starts_with("#..#.#..##"));
+    assert!(lines[11].starts_with("10: "));
+    Ok(())
+}
+
+#[test]
+fn test_parse_input() -> Result<()> {
+    let (initial, rules) = parse_str("  initial state: #..#  \n\n  ..#.# =>  #\n#.... => .  \n")?;
+    assert_eq!(initial, b"#..#");
+    assert_eq!(rules.get(b"..#.#"), b'#');
+    assert_eq!(rules.get(b"#...."), b'.');
+    assert_eq!(rules.get(b"#.#.#"), b'.');
+    Ok(())
+}
+
+#[test]
+fn test_malformed_input() {
+    let malformed = ["..#.# => ", "..#.# -> #", "..#x# => #", "..#.# => ##"];
+    for line in malformed.iter().chain(&["..#.# => # => ."]) {
+        let err = line.parse::<Rules>().err().expect(line).to_string();
+        assert!(err.starts_with("line 1: expected transition"), "{}", err);
+        let input = format!("initial state: #.#\n\n{}\n", line);
+        let err = parse_str(&input).err().expect(line).to_string();
+        assert!(err.starts_with("line 3: expected transition"), "{}", err);
+    }
+    let err = "..... => .\n\n.... => #\n".parse::<Rules>().err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 3: pattern `....` has 4 pots, expected 5"
+    );
+    let err = parse_input(&b"\nstate: #.#\n"[..]).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 2: expected `initial state: ...`, got `state: #.#`"
+    );
+    let err = parse_input(&b"initial state: #x#\n"[..]).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 1: malformed initial state `initial state: #x#`"
+    );
+    assert_eq!(
+        parse_input(&b"\n\n"[..]).err().unwrap().to_string(),
+        "empty input"
+    );
+}
+
+#[test]
+fn test_transition_lengths() {
+    for &(line, pots) in &[(".... => #", 4), ("..#.## => .", 6), (" => #", 0)] {
+        let input = format!("initial state: #.#\n\n..... => .\n{}\n", line);
+        let err = parse_input(input.as_bytes()).err().expect(line).to_string();
+        let pattern = line.split(" =>").next().unwrap();
+        assert_eq!(
+            err,
+            format!(
+                "line 4: pattern `{}` has {} pots, expected 5",
+                pattern, pots
+            )
+        );
+    }
+}
+
+#[test]
+fn test_duplicate_transitions() -> Result<()> {
+    let pots = parse_input(&b"initial state: #\n\n..#.. => #\n#.#.# => .\n..#.. => #\n"[..])?;
+    assert_eq!(pots.missing_rules().len(), 30);
+    let input = b"initial state: #\n\n..#.. => #\n#.#.# => .\n..#.. => .\n";
+    let err = parse_input(&input[..]).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "line 5: conflicting transitions for `..#..`: `#` and `.`"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_parse_input_with() -> Result<()> {
+    let rules = EXAMPLE.split_once("\n\n").unwrap().1;
+    let combined = |state: &str, rules: &str| format!("initial state: {}\n\n{}", state, rules);
+
+    // Both given separately, so stdin isn't read at all.
+    let state = "#..#.#..##......###...###";
+    let pots = parse_input_with(&b"garbage"[..], Some(state), Some(rules.as_bytes()))?;
+    assert_eq!(sum_after(pots, 20)?, 325);
+
+    let input = combined(state, "..... => .\n");
+    let pots = parse_input_with(input.as_bytes(), None, Some(rules.as_bytes()))?;
+    assert_eq!(sum_after(pots, 20)?, 325);
+    let input = combined("#", rules);
+    let pots = parse_input_with(
+        input.as_bytes(),
+        Some(&format!(" {} ", state)),
+        None::<&[u8]>,
+    )?;
+    assert_eq!(sum_after(pots, 20)?, 325);
+
+    let err = parse_input_with(&b""[..], Some("#x#"), Some(rules.as_bytes()))
+        .err()
+        .unwrap();
+    assert_eq!(err.to_string(), "malformed initial state `#x#`");
+    // A rules file has no header line, so a state line in it is just a malformed transition.
+    let err = parse_input_with(EXAMPLE.as_bytes(), None, Some(EXAMPLE.as_bytes()))
+        .err()
+        .unwrap();
+    assert!(
+        err.to_string()
+            .starts_with("rules: line 1: expected transition"),
+        "{}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<()> {
+    let (initial, mut rules) = example()?;
+    Pots::new(initial.clone(), rules.clone()).validate()?;
+    rules.transitions.remove(&b"#.#.."[..]);
+    let err = Pots::new(initial, rules).validate().unwrap_err();
+    assert_eq!(err.to_string(), "no transition for `#.#..`");
+    Ok(())
+}
+
+#[test]
+fn test_display() -> Result<()> {
+    let (initial, rules) = example()?;
+    let mut pots = Pots::new(initial, rules);
+    assert_eq!(
+        pots.to_string(),
+        "   0         1         2\n0: #..#.#..##......###...###\n"
+    );
+    for _ in 0..20 {
+        pots.evolve()?;
+    }
+    assert_eq!(
+        pots.to_string().lines().nth(1),
+        Some("-2: #....##....#####...#######....#.#..##")
+    );
+    Ok(())
+}
@@ -1,101 +1,95 @@
-use std::collections::BTreeMap;
-use std::error::Error;
-use std::io::{self, BufRead};
-use std::iter;
-use std::result;
+use d12::{parse_input_with, project_with, trace, write_csv, Result, Steady, DEFAULT_MAX_WIDTH};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
 
-type Result<T> = result::Result<T, Box<Error>>;
-
-struct Pots {
-    state: Vec<u8>,
-    transitions: BTreeMap<Vec<u8>, u8>,
-    offset: usize,
-}
-
-impl Pots {
-    fn new(mut state: Vec<u8>, transitions: BTreeMap<Vec<u8>, u8>) -> Self {
-        state.splice(0..0, iter::repeat(b'.').take(32));
-        Self { state, transitions, offset: 32 }
-    }
-
-    fn maybe_grow(&mut self) {
-        if self.state[2] == b'#' {
-            self.state.splice(0..0, iter::repeat(b'.').take(self.offset));
-            self.offset *= 2;
-        }
-        if self.state[self.state.len() - 3] == b'#' {
-            self.state.extend(iter::repeat(b'.').take(self.offset));
+fn main() -> Result<()> {
+    let mut traced = None;
+    let mut strict = false;
+    let mut generations = Vec::new();
+    let mut max_width = DEFAULT_MAX_WIDTH;
+    let mut verbose = false;
+    let mut rules = None;
+    let mut state = None;
+    let mut csv = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => traced = Some(args.next().ok_or("missing trace length")?.parse()?),
+            "--strict" => strict = true,
+            "--verbose" => verbose = true,
+            "--csv" => csv = true,
+            "--rules" => rules = Some(args.next().ok_or("missing rules file")?),
+            "--state" => state = Some(args.next().ok_or("missing initial state")?),
+            "--max-width" => max_width = args.next().ok_or("missing maximum width")?.parse()?,
+            "--generations" => {
+                generations.push(args.next().ok_or("missing generation count")?.parse()?)
+            }
+            _ => return Err(format!("unexpected argument {}", arg).into()),
         }
     }
 
-    fn evolve(&mut self) -> Result<()> {
-        self.maybe_grow();
-        let mut next = vec![b'.'; self.state.len()];
-        for i in 2..self.state.len() - 2 {
-            next[i] = *self.transitions
-                .get(&self.state[i - 2..i + 3])
-                .ok_or("no transition found")?;
+    let stdin = io::stdin();
+    let rules = match rules {
+        Some(path) => Some(BufReader::new(
+            File::open(&path).map_err(|e| format!("{}: {}", path, e))?,
+        )),
+        None => None,
+    };
+    let mut pots = parse_input_with(stdin.lock(), state.as_deref(), rules)?;
+    pots.set_max_width(max_width);
+    if strict {
+        pots.validate()?;
+    } else {
+        let missing = pots.missing_rules().len();
+        if missing > 0 {
+            eprintln!(
+                "warning: {} patterns have no rule, defaulting them to `.`",
+                missing
+            );
         }
-        self.state = next;
-        Ok(())
     }
 
-    fn trimmed(&self) -> Result<Vec<u8>> {
-        Ok(String::from_utf8(self.state.clone())?.trim_matches('.').to_owned().into_bytes())
+    if generations.is_empty() {
+        generations = vec![20, 50_000_000_000];
     }
 
-    fn sum(&self) -> i64 {
-        self.state
-            .iter()
-            .enumerate()
-            .filter(|&(_, &c)| c == b'#')
-            .map(|(i, _)| (i as i64) - (self.offset as i64))
-            .sum()
-    }
-}
-
-fn parse_transition(mut line: Vec<u8>) -> Result<(Vec<u8>, u8)> {
-    if line.len() != 10 || &line[5..9] != b" => " {
-        return Err("transition does not parse".into());
-    }
-    let to = line[9];
-    line.truncate(5);
-    Ok((line, to))
-}
-
-fn main() -> Result<()> {
-    let stdin = io::stdin();
-    let mut lines = stdin.lock().split(b'\n');
-    let mut initial = lines.next().ok_or("empty input")??;
-    if initial.len() < 15 || initial.drain(..15).collect::<Vec<u8>>() != b"initial state: " {
-        return Err("malformed initial state".into());
-    }
-    if lines.next().ok_or("premature eof")?? != b"" {
-        return Err("expected blank line".into());
+    if let Some(generations) = traced {
+        print!("{}", trace(pots.clone(), generations)?);
     }
-    let transitions = lines
-        .map(|l| parse_transition(l?))
-        .collect::<result::Result<BTreeMap<_, _>, _>>()?;
 
-    let mut pots = Pots::new(initial, transitions);
-    for _ in 0..20 {
-        pots.evolve()?;
+    // Plots the way to the furthest target instead of printing the sums.
+    if csv {
+        let stdout = io::stdout();
+        let mut out = BufWriter::new(stdout.lock());
+        write_csv(pots, *generations.iter().max().unwrap(), &mut out)?;
+        out.flush()?;
+        return Ok(());
     }
-    println!("{}", pots.sum());
 
-    let mut last_state = pots.trimmed()?;
-    for i in 20..50000000000_u64 {
-        pots.evolve()?;
-        let state = pots.trimmed()?;
-        if state == last_state {
-            let sum = pots.sum();
-            pots.evolve()?;
-            let delta = pots.sum() - sum;
-            let projection = sum + delta * (50000000000_u64 - i - 1) as i64;
-            println!("{}", projection);
-            break;
+    for &generation in &generations {
+        let (sum, steady) = project_with(pots.clone(), generation, |generation, pots| {
+            if verbose {
+                match pots.extent() {
+                    Some((first, last)) => {
+                        eprintln!("generation {}: pots {}..={}", generation, first, last)
+                    }
+                    None => eprintln!("generation {}: no live pots", generation),
+                }
+            }
+        })?;
+        println!("{}", sum);
+        if let Some(Steady {
+            generation,
+            period,
+            delta,
+        }) = steady
+        {
+            eprintln!(
+                "repeating every {} generations from generation {}, sum changing by {} each time",
+                period, generation, delta
+            );
         }
-        last_state = state;
     }
 
     Ok(())
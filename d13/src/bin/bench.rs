@@ -0,0 +1,44 @@
+use d13::{Crash, Map, Result};
+use std::io;
+use std::time::Instant;
+
+const LOOPS: usize = 100;
+const CARTS_PER_LOOP: usize = 100;
+const TICKS: usize = 1000;
+
+// Loops stacked on top of each other, each with carts heading the same way round so they never
+// meet.
+fn generate_map() -> Result<Map> {
+    let mut map = String::new();
+    for _ in 0..LOOPS {
+        map += &format!("/{}\\\n", "->".repeat(CARTS_PER_LOOP));
+        map += &format!("\\{}/\n", "--".repeat(CARTS_PER_LOOP));
+    }
+    Map::from_bytes(map.bytes().map(Ok::<u8, io::Error>))
+}
+
+fn time_ticks(name: &str, tick: fn(&mut Map) -> Result<Vec<Crash>>) -> Result<String> {
+    let mut map = generate_map()?;
+    map.validate()?;
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        if !tick(&mut map)?.is_empty() {
+            panic!("carts on the generated map crashed");
+        }
+    }
+    println!(
+        "{:>9}: {} ticks of {} carts in {:?}",
+        name,
+        TICKS,
+        LOOPS * CARTS_PER_LOOP,
+        start.elapsed()
+    );
+    Ok(map.to_string())
+}
+
+fn main() -> Result<()> {
+    let rows = time_ticks("rows", Map::tick)?;
+    let resorting = time_ticks("resorting", Map::tick_resorting)?;
+    assert!(rows == resorting, "ordered rows diverged from re-sorting");
+    Ok(())
+}
@@ -0,0 +1,1072 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+#[cfg(test)]
+use std::io;
+use std::mem;
+use std::result;
+use std::str::FromStr;
+
+pub type Result<T> = result::Result<T, MapError>;
+
+#[derive(Debug)]
+pub enum MapError {
+    Read(Box<dyn Error>),
+    InvalidByte(u8),
+    UnevenGrid,
+    OffTrack {
+        pos: Coordinates,
+    },
+    WrongDirection {
+        pos: Coordinates,
+    },
+    BrokenTrack {
+        positions: Vec<Coordinates>,
+    },
+    TickLimit {
+        max_ticks: usize,
+        carts: Vec<Coordinates>,
+    },
+    NoCrash,
+    NoRemainingCarts {
+        tick: usize,
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Read(e) => write!(f, "failed to read map: {}", e),
+            MapError::InvalidByte(byte) => write!(f, "invalid byte {:?} in map", *byte as char),
+            MapError::UnevenGrid => write!(f, "uneven grid"),
+            MapError::OffTrack { pos } => write!(f, "cart off track at {}", pos),
+            MapError::WrongDirection { pos } => {
+                write!(f, "cart at {} facing across its track", pos)
+            }
+            MapError::BrokenTrack { positions } => {
+                let positions: Vec<_> = positions.iter().map(|c| c.to_string()).collect();
+                write!(f, "track does not connect at {}", positions.join(" "))
+            }
+            MapError::TickLimit { max_ticks, carts } => {
+                let positions: Vec<_> = carts.iter().map(|c| c.to_string()).collect();
+                write!(
+                    f,
+                    "exceeded {} ticks with {} carts remaining at {}",
+                    max_ticks,
+                    carts.len(),
+                    positions.join(" ")
+                )
+            }
+            MapError::NoCrash => write!(f, "no crash"),
+            MapError::NoRemainingCarts { tick } => {
+                write!(f, "no remaining carts after tick {}", tick)
+            }
+        }
+    }
+}
+
+impl Error for MapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MapError::Read(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+enum Track {
+    Empty,
+    Vertical,
+    Horizontal,
+    Intersection,
+    CurveSlash,
+    CurveBackslash,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    N,
+    E,
+    W,
+    S,
+}
+
+impl Direction {
+    fn opposite(self) -> Direction {
+        match self {
+            Direction::N => Direction::S,
+            Direction::E => Direction::W,
+            Direction::W => Direction::E,
+            Direction::S => Direction::N,
+        }
+    }
+}
+
+impl Track {
+    // The sets of sides a piece joins up; a curve may be either of two corners.
+    fn orientations(&self) -> &'static [&'static [Direction]] {
+        use Direction::*;
+        match self {
+            Track::Empty => &[],
+            Track::Vertical => &[&[N, S]],
+            Track::Horizontal => &[&[E, W]],
+            Track::Intersection => &[&[N, E, W, S]],
+            Track::CurveSlash => &[&[S, E], &[N, W]],
+            Track::CurveBackslash => &[&[S, W], &[N, E]],
+        }
+    }
+
+    fn connects(&self, side: Direction) -> bool {
+        self.orientations()
+            .iter()
+            .any(|sides| sides.contains(&side))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Coordinates(pub usize, pub usize);
+
+impl PartialOrd for Coordinates {
+    fn partial_cmp(&self, other: &Coordinates) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Coordinates {
+    fn cmp(&self, other: &Coordinates) -> Ordering {
+        (self.1, self.0).cmp(&(other.1, other.0))
+    }
+}
+
+impl fmt::Display for Coordinates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        write!(f, "{},{}", self.0, self.1)
+    }
+}
+
+impl Coordinates {
+    fn shift(&mut self, direction: Direction) {
+        match direction {
+            Direction::N => self.1 = self.1.wrapping_sub(1),
+            Direction::E => self.0 += 1,
+            Direction::W => self.0 = self.0.wrapping_sub(1),
+            Direction::S => self.1 += 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum OnIntersection {
+    Left,
+    Straight,
+    Right,
+}
+
+#[derive(Clone, Copy)]
+struct Cart {
+    // The cart's index in reading order at the start.
+    id: usize,
+    position: Coordinates,
+    direction: Direction,
+    on_intersection: OnIntersection,
+}
+
+impl Cart {
+    fn move_on_track(&mut self, track: &Track) -> Result<()> {
+        use Direction::*;
+        let pos = self.position;
+        self.direction = match track {
+            Track::Empty => return Err(MapError::OffTrack { pos }),
+            Track::Vertical => match self.direction {
+                N | S => self.direction,
+                _ => return Err(MapError::WrongDirection { pos }),
+            },
+            Track::Horizontal => match self.direction {
+                E | W => self.direction,
+                _ => return Err(MapError::WrongDirection { pos }),
+            },
+            Track::Intersection => match self.on_intersection {
+                OnIntersection::Left => {
+                    self.on_intersection = OnIntersection::Straight;
+                    match self.direction {
+                        N => W,
+                        E => N,
+                        W => S,
+                        S => E,
+                    }
+                }
+                OnIntersection::Straight => {
+                    self.on_intersection = OnIntersection::Right;
+                    self.direction
+                }
+                OnIntersection::Right => {
+                    self.on_intersection = OnIntersection::Left;
+                    match self.direction {
+                        N => E,
+                        E => S,
+                        W => N,
+                        S => W,
+                    }
+                }
+            },
+            Track::CurveSlash => match self.direction {
+                N => E,
+                E => N,
+                W => S,
+                S => W,
+            },
+            Track::CurveBackslash => match self.direction {
+                N => W,
+                E => S,
+                W => N,
+                S => E,
+            },
+        };
+        self.position.shift(self.direction);
+        Ok(())
+    }
+}
+
+pub const DEFAULT_MAX_TICKS: usize = 1_000_000;
+
+// Carts are identified by their index in reading order on the original map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Involved {
+    // Two carts colliding, lowest first.
+    Carts(usize, usize),
+    // A cart running into wreckage left by an earlier crash.
+    Wreckage(usize),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Crash {
+    pub tick: usize,
+    pub position: Coordinates,
+    pub involved: Involved,
+}
+
+impl fmt::Display for Crash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (tick, position) = (self.tick, self.position);
+        match self.involved {
+            Involved::Carts(a, b) => {
+                write!(
+                    f,
+                    "tick {}: crash at {} between carts {} and {}",
+                    tick, position, a, b
+                )
+            }
+            Involved::Wreckage(a) => {
+                write!(
+                    f,
+                    "tick {}: cart {} ran into wreckage at {}",
+                    tick, a, position
+                )
+            }
+        }
+    }
+}
+
+// The id of the cart in each square is kept alongside the track, so finding crashes needs no
+// hashing. Carts are kept by row, in order along it, so they're always in the order they move in:
+// a cart can't pass another in its row without crashing, and moving up or down a row only shifts
+// the carts in the two rows. `cargo run --release --bin bench` times 10,000 carts this way and by
+// re-sorting them all every tick.
+pub struct Map {
+    width: usize,
+    tracks: Vec<Track>,
+    rows: Vec<Vec<Cart>>,
+    occupied: Vec<Option<usize>>,
+    ticks: usize,
+    pub max_ticks: usize,
+    // Whether crashes leave wreckage that wrecks any cart arriving there later.
+    pub wreckage: bool,
+    wrecks: HashSet<Coordinates>,
+}
+
+fn create_cart(id: usize, position: Coordinates, direction: Direction) -> Cart {
+    Cart {
+        id,
+        position,
+        direction,
+        on_intersection: OnIntersection::Left,
+    }
+}
+
+impl Map {
+    // Reads the map a row at a time, padding rows shorter than the widest with empty space since
+    // editors often strip trailing spaces.
+    pub fn from_bytes<T, E>(bytes: T) -> Result<Map>
+    where
+        T: Iterator<Item = result::Result<u8, E>>,
+        E: Error + 'static,
+    {
+        Map::read(bytes, false)
+    }
+
+    // Like `from_bytes`, but rejects rows of different lengths.
+    pub fn from_bytes_strict<T, E>(bytes: T) -> Result<Map>
+    where
+        T: Iterator<Item = result::Result<u8, E>>,
+        E: Error + 'static,
+    {
+        Map::read(bytes, true)
+    }
+
+    fn read<T, E>(bytes: T, strict: bool) -> Result<Map>
+    where
+        T: Iterator<Item = result::Result<u8, E>>,
+        E: Error + 'static,
+    {
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        let mut carts = Vec::new();
+        for byte in bytes {
+            let byte = byte.map_err(|e| MapError::Read(Box::new(e)))?;
+            let position = Coordinates(row.len(), rows.len());
+            match byte {
+                b' ' => row.push(Track::Empty),
+                b'|' => row.push(Track::Vertical),
+                b'-' => row.push(Track::Horizontal),
+                b'/' => row.push(Track::CurveSlash),
+                b'\\' => row.push(Track::CurveBackslash),
+                b'+' => row.push(Track::Intersection),
+                b'^' => {
+                    carts.push(create_cart(carts.len(), position, Direction::N));
+                    row.push(Track::Vertical);
+                }
+                b'v' => {
+                    carts.push(create_cart(carts.len(), position, Direction::S));
+                    row.push(Track::Vertical);
+                }
+                b'<' => {
+                    carts.push(create_cart(carts.len(), position, Direction::W));
+                    row.push(Track::Horizontal);
+                }
+                b'>' => {
+                    carts.push(create_cart(carts.len(), position, Direction::E));
+                    row.push(Track::Horizontal);
+                }
+                b'\n' => rows.push(mem::take(&mut row)),
+                _ => return Err(MapError::InvalidByte(byte)),
+            }
+        }
+        if !row.is_empty() {
+            rows.push(row);
+        }
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if strict && rows.iter().any(|row| row.len() != width) {
+            return Err(MapError::UnevenGrid);
+        }
+        let height = rows.len();
+        let mut tracks = Vec::with_capacity(width * height);
+        for mut row in rows {
+            row.resize_with(width, || Track::Empty);
+            tracks.append(&mut row);
+        }
+        let mut occupied = vec![None; tracks.len()];
+        for cart in &carts {
+            let Coordinates(x, y) = cart.position;
+            occupied[x + y * width] = Some(cart.id);
+        }
+        // Carts are found in reading order, so each row's are already in order.
+        let mut cart_rows = vec![Vec::new(); height];
+        for cart in carts {
+            cart_rows[cart.position.1].push(cart);
+        }
+        Ok(Map {
+            width,
+            tracks,
+            rows: cart_rows,
+            occupied,
+            ticks: 0,
+            max_ticks: DEFAULT_MAX_TICKS,
+            wreckage: false,
+            wrecks: HashSet::new(),
+        })
+    }
+
+    fn carts(&self) -> impl Iterator<Item = &Cart> {
+        self.rows.iter().flatten()
+    }
+
+    fn cart_count(&self) -> usize {
+        self.rows.iter().map(Vec::len).sum()
+    }
+
+    // Removes the cart at `position`, which must have one.
+    fn remove_cart(&mut self, Coordinates(x, y): Coordinates) -> Cart {
+        let row = &mut self.rows[y];
+        let i = row.partition_point(|c| c.position.0 < x);
+        row.remove(i)
+    }
+
+    fn insert_cart(&mut self, cart: Cart) {
+        let Coordinates(x, y) = cart.position;
+        let row = &mut self.rows[y];
+        let i = row.partition_point(|c| c.position.0 < x);
+        row.insert(i, cart);
+    }
+
+    fn track_at(&self, Coordinates(x, y): Coordinates) -> &Track {
+        &self.tracks[x + y * self.width]
+    }
+
+    fn neighbor(&self, Coordinates(x, y): Coordinates, side: Direction) -> Option<&Track> {
+        let height = self.tracks.len() / self.width;
+        let position = match side {
+            Direction::N if y > 0 => Coordinates(x, y - 1),
+            Direction::E if x + 1 < self.width => Coordinates(x + 1, y),
+            Direction::W if x > 0 => Coordinates(x - 1, y),
+            Direction::S if y + 1 < height => Coordinates(x, y + 1),
+            _ => return None,
+        };
+        Some(self.track_at(position))
+    }
+
+    // Checks that every piece of track, including those under carts, joins up with its neighbors
+    // on all the sides it needs to.
+    pub fn validate(&self) -> Result<()> {
+        let mut positions = Vec::new();
+        for (i, track) in self.tracks.iter().enumerate() {
+            let position = Coordinates(i % self.width, i / self.width);
+            let connected = track.orientations().iter().any(|sides| {
+                sides.iter().all(|&side| {
+                    self.neighbor(position, side)
+                        .is_some_and(|neighbor| neighbor.connects(side.opposite()))
+                })
+            });
+            if !connected && !track.orientations().is_empty() {
+                positions.push(position);
+            }
+        }
+        for cart in self.carts() {
+            if !self.track_at(cart.position).connects(cart.direction) {
+                positions.push(cart.position);
+            }
+        }
+        if positions.is_empty() {
+            Ok(())
+        } else {
+            positions.sort();
+            positions.dedup();
+            Err(MapError::BrokenTrack { positions })
+        }
+    }
+
+    pub fn tick(&mut self) -> Result<Vec<Crash>> {
+        if self.ticks >= self.max_ticks {
+            return Err(MapError::TickLimit {
+                max_ticks: self.max_ticks,
+                carts: self.carts().map(|c| c.position).collect(),
+            });
+        }
+        self.ticks += 1;
+        let mut crashes = Vec::new();
+        let (width, len) = (self.width, self.tracks.len());
+        let index =
+            |Coordinates(x, y): Coordinates| Some(x + y * width).filter(|&i| x < width && i < len);
+        // A cart that's been crashed into is no longer in its square, and one that's since moved
+        // into the square it left has already had its turn.
+        let order: Vec<_> = self.carts().map(|c| (c.position, c.id)).collect();
+        for (start, id) in order {
+            let from = index(start).unwrap();
+            if self.occupied[from] != Some(id) {
+                continue;
+            }
+            let row = &mut self.rows[start.1];
+            let i = row.partition_point(|c| c.position.0 < start.0);
+            let mut cart = row[i];
+            cart.move_on_track(&self.tracks[from])?;
+            self.occupied[from] = None;
+            let pos = cart.position;
+            // Driving off the edge of the map is reported where the cart left it.
+            let to = index(pos).ok_or(MapError::OffTrack { pos: start })?;
+            // Nothing is wrecked without wreckage, so this never matches.
+            if self.wrecks.contains(&pos) {
+                self.rows[start.1].remove(i);
+                let involved = Involved::Wreckage(cart.id);
+                crashes.push(Crash {
+                    tick: self.ticks,
+                    position: pos,
+                    involved,
+                });
+            } else if let Some(other) = self.occupied[to].take() {
+                self.rows[start.1].remove(i);
+                self.remove_cart(pos);
+                let involved = Involved::Carts(cart.id.min(other), cart.id.max(other));
+                crashes.push(Crash {
+                    tick: self.ticks,
+                    position: pos,
+                    involved,
+                });
+                if self.wreckage {
+                    self.wrecks.insert(pos);
+                }
+            } else {
+                self.occupied[to] = Some(cart.id);
+                if pos.1 == start.1 {
+                    self.rows[start.1][i] = cart;
+                } else {
+                    self.rows[start.1].remove(i);
+                    self.insert_cart(cart);
+                }
+            }
+        }
+        Ok(crashes)
+    }
+
+    // Like `tick`, but with every cart in one list that's sorted again after each tick, as before
+    // they were kept in ordered rows. Only there for the bench to compare against.
+    pub fn tick_resorting(&mut self) -> Result<Vec<Crash>> {
+        if self.ticks >= self.max_ticks {
+            return Err(MapError::TickLimit {
+                max_ticks: self.max_ticks,
+                carts: self.carts().map(|c| c.position).collect(),
+            });
+        }
+        self.ticks += 1;
+        let mut crashes = Vec::new();
+        let (width, len) = (self.width, self.tracks.len());
+        let index =
+            |Coordinates(x, y): Coordinates| Some(x + y * width).filter(|&i| x < width && i < len);
+        let mut carts: Vec<_> = self.rows.iter_mut().flat_map(mem::take).collect();
+        // A cart that's been crashed into is no longer in its square.
+        for cart in &mut carts {
+            let from = index(cart.position).unwrap();
+            if self.occupied[from] != Some(cart.id) {
+                continue;
+            }
+            let start = cart.position;
+            cart.move_on_track(&self.tracks[from])?;
+            self.occupied[from] = None;
+            let pos = cart.position;
+            let to = index(pos).ok_or(MapError::OffTrack { pos: start })?;
+            if self.wrecks.contains(&pos) {
+                let involved = Involved::Wreckage(cart.id);
+                crashes.push(Crash {
+                    tick: self.ticks,
+                    position: pos,
+                    involved,
+                });
+            } else if let Some(other) = self.occupied[to].take() {
+                let involved = Involved::Carts(cart.id.min(other), cart.id.max(other));
+                crashes.push(Crash {
+                    tick: self.ticks,
+                    position: pos,
+                    involved,
+                });
+                if self.wreckage {
+                    self.wrecks.insert(pos);
+                }
+            } else {
+                self.occupied[to] = Some(cart.id);
+            }
+        }
+        let occupied = &self.occupied;
+        carts.retain(|c| index(c.position).and_then(|i| occupied[i]) == Some(c.id));
+        carts.sort_by_key(|c| c.position);
+        for cart in carts {
+            self.rows[cart.position.1].push(cart);
+        }
+        Ok(crashes)
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> result::Result<(), fmt::Error> {
+        for (i, track) in self.tracks.iter().enumerate() {
+            let mut ch = match track {
+                Track::Empty => ' ',
+                Track::Vertical => '|',
+                Track::Horizontal => '-',
+                Track::Intersection => '+',
+                Track::CurveSlash => '/',
+                Track::CurveBackslash => '\\',
+            };
+            let position = Coordinates(i % self.width, i / self.width);
+            if self.wrecks.contains(&position) {
+                ch = 'X';
+            }
+            let row = &self.rows[position.1];
+            let cart = row.iter().find(|c| c.position == position);
+            if let Some(cart) = cart {
+                ch = match cart.direction {
+                    Direction::N => '^',
+                    Direction::E => '>',
+                    Direction::W => '<',
+                    Direction::S => 'v',
+                }
+            }
+            write!(f, "{}", ch)?;
+            if (i + 1) % self.width == 0 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Runs until the first crash, calling `on_tick` with the map after every tick, and returns where
+// it happened.
+pub fn first_crash_with(map: &mut Map, mut on_tick: impl FnMut(&Map)) -> Result<Coordinates> {
+    while map.cart_count() > 1 {
+        let crashes = map.tick()?;
+        on_tick(map);
+        if let Some(crash) = crashes.first() {
+            return Ok(crash.position);
+        }
+    }
+    Err(MapError::NoCrash)
+}
+
+// Runs until at most one cart is left, calling `on_tick` with the map after every tick, and
+// returns the final position of the last cart.
+pub fn last_cart_with(map: &mut Map, mut on_tick: impl FnMut(&Map)) -> Result<Coordinates> {
+    while map.cart_count() > 1 {
+        map.tick()?;
+        on_tick(map);
+    }
+    let last_cart = map.carts().next();
+    last_cart
+        .map(|c| c.position)
+        .ok_or(MapError::NoRemainingCarts { tick: map.ticks })
+}
+
+// Runs until at most one cart is left, calling `on_tick` with the map after every tick, and
+// returns every crash on the way.
+pub fn all_crashes_with(map: &mut Map, mut on_tick: impl FnMut(&Map)) -> Result<Vec<Crash>> {
+    let mut all = Vec::new();
+    while map.cart_count() > 1 {
+        all.extend(map.tick()?);
+        on_tick(map);
+    }
+    Ok(all)
+}
+
+// Every crash with wreckage, and where the last cart is once it's alone, unless the last ones
+// wrecked together.
+#[derive(Debug, PartialEq)]
+pub struct Wrecked {
+    pub crashes: Vec<Crash>,
+    pub last_cart: Option<Coordinates>,
+}
+
+// Turns on wreckage and runs until at most one cart is left, calling `on_tick` with the map after
+// every tick. A lone cart might never reach a wreck, so it isn't run any further.
+pub fn wreck_all_with(map: &mut Map, mut on_tick: impl FnMut(&Map)) -> Result<Wrecked> {
+    map.wreckage = true;
+    let mut crashes = Vec::new();
+    while map.cart_count() > 1 {
+        crashes.extend(map.tick()?);
+        on_tick(map);
+    }
+    let last_cart = map.carts().next().map(|c| c.position);
+    Ok(Wrecked { crashes, last_cart })
+}
+
+pub fn first_crash(map: &mut Map) -> Result<Coordinates> {
+    first_crash_with(map, |_| {})
+}
+
+pub fn last_cart(map: &mut Map) -> Result<Coordinates> {
+    last_cart_with(map, |_| {})
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Part {
+    One,
+    Two,
+    Both,
+}
+
+impl FromStr for Part {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Part, String> {
+        match s {
+            "1" => Ok(Part::One),
+            "2" => Ok(Part::Two),
+            "both" => Ok(Part::Both),
+            _ => Err(format!("expected part 1, 2 or both, got `{}`", s)),
+        }
+    }
+}
+
+// The answers to whichever parts were asked for.
+#[derive(Debug, PartialEq)]
+pub struct Answer {
+    pub first_crash: Option<Coordinates>,
+    pub last_cart: Option<Coordinates>,
+}
+
+impl Answer {
+    pub fn to_json(&self) -> String {
+        let fields: Vec<_> = [
+            ("first_crash", self.first_crash),
+            ("last_cart", self.last_cart),
+        ]
+        .iter()
+        .filter_map(|&(name, pos)| {
+            pos.map(|Coordinates(x, y)| format!("\"{}\":{{\"x\":{},\"y\":{}}}", name, x, y))
+        })
+        .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+// Runs the map for the parts asked for, continuing from the first crash to find the last cart
+// when both are.
+pub fn solve_with(map: &mut Map, part: Part, mut on_tick: impl FnMut(&Map)) -> Result<Answer> {
+    let first_crash = match part {
+        Part::One | Part::Both => Some(first_crash_with(map, &mut on_tick)?),
+        Part::Two => None,
+    };
+    let last_cart = match part {
+        Part::Two | Part::Both => Some(last_cart_with(map, &mut on_tick)?),
+        Part::One => None,
+    };
+    Ok(Answer {
+        first_crash,
+        last_cart,
+    })
+}
+
+#[cfg(test)]
+fn parse(map: &str) -> Result<Map> {
+    Map::from_bytes(map.bytes().map(Ok::<u8, io::Error>))
+}
+
+#[cfg(test)]
+const EXAMPLE_CRASH: &str = r"/->-\        
+|   |  /----\
+| /-+--+-\  |
+| | |  | v  |
+\-+-/  \-+--/
+  \------/   
+";
+
+#[cfg(test)]
+const EXAMPLE_LAST_CART: &str = r"/>-<\  
+|   |  
+| /<+-\
+| | | v
+\>+</ |
+  |   ^
+  \<->/
+";
+
+#[test]
+fn test_run() -> Result<()> {
+    assert_eq!(first_crash(&mut parse(EXAMPLE_CRASH)?)?, Coordinates(7, 3));
+    let err = last_cart(&mut parse(EXAMPLE_CRASH)?).unwrap_err();
+    assert_eq!(err.to_string(), "no remaining carts after tick 14");
+
+    assert_eq!(
+        first_crash(&mut parse(EXAMPLE_LAST_CART)?)?,
+        Coordinates(2, 0)
+    );
+    assert_eq!(
+        last_cart(&mut parse(EXAMPLE_LAST_CART)?)?,
+        Coordinates(6, 4)
+    );
+    assert!(matches!(
+        first_crash(&mut parse("->-\n")?),
+        Err(MapError::NoCrash)
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_solve() -> Result<()> {
+    let both = solve_with(&mut parse(EXAMPLE_LAST_CART)?, Part::Both, |_| {})?;
+    let (first_crash, last_cart) = (Some(Coordinates(2, 0)), Some(Coordinates(6, 4)));
+    assert_eq!(
+        both,
+        Answer {
+            first_crash,
+            last_cart
+        }
+    );
+    assert_eq!(
+        both.to_json(),
+        r#"{"first_crash":{"x":2,"y":0},"last_cart":{"x":6,"y":4}}"#
+    );
+
+    let mut ticks = 0;
+    let one = solve_with(&mut parse(EXAMPLE_CRASH)?, Part::One, |_| ticks += 1)?;
+    assert_eq!(
+        one,
+        Answer {
+            first_crash: Some(Coordinates(7, 3)),
+            last_cart: None
+        }
+    );
+    assert_eq!(one.to_json(), r#"{"first_crash":{"x":7,"y":3}}"#);
+    assert_eq!(ticks, 14);
+    let two = solve_with(&mut parse(EXAMPLE_LAST_CART)?, Part::Two, |_| {})?;
+    assert_eq!(two.to_json(), r#"{"last_cart":{"x":6,"y":4}}"#);
+
+    assert_eq!("both".parse::<Part>(), Ok(Part::Both));
+    assert_eq!(
+        "3".parse::<Part>().unwrap_err(),
+        "expected part 1, 2 or both, got `3`"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_ragged() -> Result<()> {
+    // The example with trailing spaces stripped, and no newline after the last row.
+    let ragged: Vec<_> = EXAMPLE_CRASH.lines().map(str::trim_end).collect();
+    let ragged = ragged.join("\n");
+    let mut map = parse(&ragged)?;
+    assert_eq!(map.to_string(), parse(EXAMPLE_CRASH)?.to_string());
+    map.validate()?;
+    let mut first_crash = None;
+    while first_crash.is_none() {
+        first_crash = map.tick()?.first().map(|crash| crash.position);
+    }
+    assert_eq!(first_crash, Some(Coordinates(7, 3)));
+
+    let ragged: Vec<_> = EXAMPLE_LAST_CART.lines().map(str::trim_end).collect();
+    let ragged = ragged.join("\n") + "\n";
+    assert_eq!(
+        last_cart(&mut parse(&ragged)?)?,
+        last_cart(&mut parse(EXAMPLE_LAST_CART)?)?
+    );
+    let strict = Map::from_bytes_strict(ragged.bytes().map(Ok::<u8, io::Error>));
+    assert!(matches!(strict, Err(MapError::UnevenGrid)));
+    Map::from_bytes_strict(EXAMPLE_LAST_CART.bytes().map(Ok::<u8, io::Error>))?;
+    Ok(())
+}
+
+#[test]
+fn test_all_crashes() -> Result<()> {
+    let mut map = parse("->-<-\n->---<-\n")?;
+    let log: Vec<_> = all_crashes_with(&mut map, |_| {})?
+        .iter()
+        .map(Crash::to_string)
+        .collect();
+    assert_eq!(
+        log,
+        [
+            "tick 1: crash at 2,0 between carts 0 and 1",
+            "tick 2: crash at 3,1 between carts 2 and 3"
+        ]
+    );
+    assert!(matches!(
+        last_cart(&mut map),
+        Err(MapError::NoRemainingCarts { tick: 2 })
+    ));
+
+    // The first example's carts run into each other on their fourteenth tick, and the other's
+    // leave one of their number.
+    let crashes = all_crashes_with(&mut parse(EXAMPLE_CRASH)?, |_| {})?;
+    let involved = Involved::Carts(0, 1);
+    assert_eq!(
+        crashes,
+        [Crash {
+            tick: 14,
+            position: Coordinates(7, 3),
+            involved
+        }]
+    );
+    let mut map = parse(EXAMPLE_LAST_CART)?;
+    let crashes = all_crashes_with(&mut map, |_| {})?;
+    assert_eq!(crashes.len(), 4);
+    assert_eq!(
+        crashes[0].to_string(),
+        "tick 1: crash at 2,0 between carts 0 and 1"
+    );
+    assert_eq!(last_cart(&mut map)?, Coordinates(6, 4));
+    Ok(())
+}
+
+#[test]
+fn test_wreckage() -> Result<()> {
+    // Carts 0 and 1 crash at 2,0, and cart 2 runs into what they left three ticks later, leaving
+    // cart 3 alone.
+    let row = "->-<--<--<\n";
+    let wrecked = wreck_all_with(&mut parse(row)?, |_| {})?;
+    let log: Vec<_> = wrecked.crashes.iter().map(Crash::to_string).collect();
+    assert_eq!(
+        log,
+        [
+            "tick 1: crash at 2,0 between carts 0 and 1",
+            "tick 4: cart 2 ran into wreckage at 2,0"
+        ]
+    );
+    assert_eq!(wrecked.last_cart, Some(Coordinates(5, 0)));
+    let mut map = parse(row)?;
+    map.wreckage = true;
+    map.tick()?;
+    assert_eq!(map.to_string(), "--X--<--<-\n");
+    // A cart left alone by the first crash is the last cart straight away, even though it would
+    // later run into the wreck.
+    let row = "->-<--<\n";
+    let wrecked = wreck_all_with(&mut parse(row)?, |_| {})?;
+    assert_eq!(wrecked.crashes.len(), 1);
+    assert_eq!(wrecked.last_cart, Some(last_cart(&mut parse(row)?)?));
+
+    // The examples' first crashes are the same either way, and the first's is also its last.
+    let mut map = parse(EXAMPLE_CRASH)?;
+    map.wreckage = true;
+    assert_eq!(first_crash(&mut map)?, Coordinates(7, 3));
+    let mut map = parse(EXAMPLE_LAST_CART)?;
+    map.wreckage = true;
+    assert_eq!(first_crash(&mut map)?, Coordinates(2, 0));
+    let wrecked = wreck_all_with(&mut parse(EXAMPLE_CRASH)?, |_| {})?;
+    assert_eq!(wrecked.crashes.len(), 1);
+    assert_eq!(wrecked.last_cart, None);
+    let wrecked = wreck_all_with(&mut parse(EXAMPLE_LAST_CART)?, |_| {})?;
+    let log: Vec<_> = wrecked.crashes.iter().map(Crash::to_string).collect();
+    // The survivor without wreckage, and the two carts left with it, run into the wrecks from
+    // the first tick.
+    assert_eq!(
+        log,
+        [
+            "tick 1: crash at 2,0 between carts 0 and 1",
+            "tick 1: crash at 2,4 between carts 4 and 5",
+            "tick 1: crash at 6,4 between carts 3 and 6",
+            "tick 3: cart 2 ran into wreckage at 2,4",
+            "tick 3: cart 7 ran into wreckage at 2,4",
+            "tick 3: cart 8 ran into wreckage at 6,4",
+        ]
+    );
+    assert_eq!(wrecked.last_cart, None);
+    Ok(())
+}
+
+#[test]
+fn test_max_ticks() -> Result<()> {
+    let mut map = parse("/->-\\\n|   |\n\\-<-/\n")?;
+    map.max_ticks = 100;
+    for _ in 0..100 {
+        assert!(map.tick()?.is_empty());
+    }
+    match map.tick() {
+        Err(MapError::TickLimit {
+            max_ticks: 100,
+            carts,
+        }) => {
+            assert_eq!(carts, [Coordinates(0, 0), Coordinates(4, 2)]);
+        }
+        _ => panic!("expected tick limit error"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_validate() -> Result<()> {
+    parse(EXAMPLE_CRASH)?.validate()?;
+    parse(EXAMPLE_LAST_CART)?.validate()?;
+    match parse("/-\\\n| |\n> |\n| |\n\\-/\n")?.validate() {
+        Err(MapError::BrokenTrack { positions }) => {
+            assert_eq!(
+                positions,
+                [Coordinates(0, 1), Coordinates(0, 2), Coordinates(0, 3)]
+            );
+        }
+        _ => panic!("expected broken track"),
+    }
+
+    // A curve turned the wrong way at the top right, leaving it joined only to the left and below.
+    let broken_curve = parse("/-/\n| |\n\\-/\n")?.validate();
+    assert_eq!(
+        broken_curve.unwrap_err().to_string(),
+        "track does not connect at 2,0"
+    );
+    // A cart heading east off the end of a stub of track into empty space.
+    let off_the_end = parse("/-\\  \n| |-> \n\\-/  \n")?.validate();
+    assert_eq!(
+        off_the_end.unwrap_err().to_string(),
+        "track does not connect at 3,1 4,1"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_map_errors() -> Result<()> {
+    assert!(matches!(
+        parse("-->-\nx\n"),
+        Err(MapError::InvalidByte(b'x'))
+    ));
+    let uneven = "-->-\n--\n";
+    let strict = Map::from_bytes_strict(uneven.bytes().map(Ok::<u8, io::Error>));
+    assert!(matches!(strict, Err(MapError::UnevenGrid)));
+
+    let mut map = parse("-> \n")?;
+    map.tick()?;
+    assert!(matches!(
+        map.tick(),
+        Err(MapError::OffTrack {
+            pos: Coordinates(2, 0)
+        })
+    ));
+
+    // Off the edge of the map rather than onto empty space.
+    let mut map = parse("<-\n->\n")?;
+    assert!(matches!(
+        map.tick(),
+        Err(MapError::OffTrack {
+            pos: Coordinates(0, 0)
+        })
+    ));
+    let mut map = parse("|-\n->\n")?;
+    assert!(matches!(
+        map.tick(),
+        Err(MapError::OffTrack {
+            pos: Coordinates(1, 1)
+        })
+    ));
+
+    let mut map = parse(" v \n---\n")?;
+    map.tick()?;
+    assert!(matches!(
+        map.tick(),
+        Err(MapError::WrongDirection {
+            pos: Coordinates(1, 1)
+        })
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_many_carts() -> Result<()> {
+    // 100 loops stacked on top of each other, each with 100 carts heading the same way round so
+    // they never meet.
+    let mut map = String::new();
+    for _ in 0..100 {
+        map += &format!("/{}\\\n", "->".repeat(100));
+        map += &format!("\\{}/\n", "--".repeat(100));
+    }
+    let mut map = parse(&map)?;
+    map.validate()?;
+    for _ in 0..100 {
+        assert!(map.tick()?.is_empty());
+        let positions: Vec<_> = map.carts().map(|c| c.position).collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+    }
+    assert_eq!(map.cart_count(), 10_000);
+    let first: Vec<_> = map.carts().take(2).map(|c| c.position).collect();
+    assert_eq!(first, [Coordinates(102, 0), Coordinates(104, 0)]);
+    Ok(())
+}
+
+#[test]
+fn test_tick_resorting() -> Result<()> {
+    for example in &[EXAMPLE_CRASH, EXAMPLE_LAST_CART] {
+        for &wreckage in &[false, true] {
+            let (mut rows, mut resorting) = (parse(example)?, parse(example)?);
+            rows.wreckage = wreckage;
+            resorting.wreckage = wreckage;
+            while rows.cart_count() > 1 {
+                assert_eq!(rows.tick()?, resorting.tick_resorting()?);
+                assert_eq!(rows.to_string(), resorting.to_string());
+            }
+        }
+    }
+    Ok(())
+}
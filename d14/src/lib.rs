@@ -0,0 +1,215 @@
+use std::error::Error;
+use std::result;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+/// Yields the digits of the recipe board, starting with the initial recipes. New recipes are
+/// generated lazily as they're needed and the sequence never ends.
+///
+/// ```
+/// use d14::Scores;
+///
+/// let digits: Vec<usize> = Scores::new(&[3, 7]).skip(9).take(10).collect();
+/// assert_eq!(digits, [5, 1, 5, 8, 9, 1, 6, 7, 7, 9]);
+/// ```
+pub struct Scores {
+    scores: Vec<usize>,
+    elves: [usize; 2],
+    hold: Vec<usize>,
+}
+
+impl Iterator for Scores {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if !self.hold.is_empty() {
+            return self.hold.pop();
+        }
+
+        let sum = self.elves.iter().map(|&e| self.scores[e]).sum::<usize>();
+        let result = if sum > 9 {
+            assert!(sum < 20);
+            self.scores.push(sum / 10);
+            self.scores.push(sum % 10);
+            self.hold.push(sum % 10);
+            sum / 10
+        } else {
+            self.scores.push(sum);
+            sum
+        };
+
+        for elf in &mut self.elves {
+            *elf = (*elf + self.scores[*elf] + 1) % self.scores.len();
+        }
+        Some(result)
+    }
+}
+
+impl Scores {
+    /// The two elves start on the first two of `initial`, which must be single digits.
+    pub fn new(initial: &[usize]) -> Self {
+        assert!(initial.len() >= 2, "need at least two initial recipes");
+        assert!(
+            initial.iter().all(|&score| score < 10),
+            "recipe scores must be single digits"
+        );
+        Self {
+            scores: initial.to_vec(),
+            elves: [0, 1],
+            hold: initial.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+// Parses seed scores given as separate arguments, comma-separated, or both: `3 7` or `3,7`.
+pub fn parse_seed(args: &[String]) -> Result<Vec<usize>> {
+    let mut seed = Vec::new();
+    for score in args.iter().flat_map(|arg| arg.split(',')) {
+        let score: usize = score.trim().parse()?;
+        if score > 9 {
+            return Err(format!("recipe score {} is not a single digit", score).into());
+        }
+        seed.push(score);
+    }
+    if seed.len() < 2 {
+        return Err("need at least two initial recipes".into());
+    }
+    Ok(seed)
+}
+
+// The digits taken off a `Scores` so far, so that both parts can be answered from one run of the
+// generator.
+pub struct Board {
+    scores: Scores,
+    digits: Vec<usize>,
+}
+
+impl Board {
+    pub fn new(scores: Scores) -> Self {
+        Self {
+            scores,
+            digits: Vec::new(),
+        }
+    }
+
+    // The first `len` digits, generating only those not already on the board.
+    fn digits(&mut self, len: usize) -> &[usize] {
+        if self.digits.len() < len {
+            let more = len - self.digits.len();
+            self.digits.extend(self.scores.by_ref().take(more));
+        }
+        &self.digits[..len]
+    }
+}
+
+// The ten scores after the first `iterations` recipes.
+pub fn ten_after(board: &mut Board, iterations: usize) -> String {
+    board.digits(iterations + 10)[iterations..]
+        .iter()
+        .map(|score| score.to_string())
+        .collect()
+}
+
+// The number of recipes before `pattern` first appears. Digits are checked one at a time as they
+// come off the iterator, so a match that straddles a two-digit append is found at the digit that
+// completes it.
+pub fn first_index(board: &mut Board, pattern: &[usize]) -> usize {
+    let mut len = pattern.len();
+    while !board.digits(len).ends_with(pattern) {
+        len += 1;
+    }
+    len - pattern.len()
+}
+
+// Answers both parts for a board seeded with `seed`: the ten scores after `input` recipes, and
+// the number of recipes before the digits of `input` first appear.
+pub fn solve_with(seed: &[usize], input: &str) -> Result<(String, usize)> {
+    let iterations: usize = input.parse()?;
+    let mut score_pattern = Vec::new();
+    for c in input.bytes() {
+        if !c.is_ascii_digit() {
+            return Err(format!("invalid digit {:?} in {:?}", c as char, input).into());
+        }
+        score_pattern.push((c - b'0') as usize);
+    }
+    let mut board = Board::new(Scores::new(seed));
+    Ok((
+        ten_after(&mut board, iterations),
+        first_index(&mut board, &score_pattern),
+    ))
+}
+
+pub fn solve(input: &str) -> Result<(String, usize)> {
+    solve_with(&[3, 7], input)
+}
+
+#[test]
+fn test_solve() -> Result<()> {
+    assert_eq!(solve("9")?.0, "5158916779");
+    assert_eq!(solve("5")?.0, "0124515891");
+    assert_eq!(solve("18")?.0, "9251071085");
+    assert_eq!(solve("2018")?.0, "5941429882");
+    assert_eq!(solve("51589")?.1, 9);
+    assert_eq!(solve("01245")?.1, 5);
+    assert_eq!(solve("92510")?.1, 18);
+    assert_eq!(solve("59414")?.1, 2018);
+    Ok(())
+}
+
+#[test]
+fn test_adapters() {
+    let mut board = Board::new(Scores::new(&[3, 7]));
+    assert_eq!(ten_after(&mut board, 2018), "5941429882");
+    assert_eq!(board.digits.len(), 2028);
+    // Both are within the digits the first already generated.
+    assert_eq!(first_index(&mut board, &[5, 9, 4, 1, 4]), 2018);
+    assert_eq!(first_index(&mut board, &[1, 0, 1, 2]), 4);
+    assert_eq!(board.digits.len(), 2028);
+    assert_eq!(ten_after(&mut board, 9), "5158916779");
+}
+
+#[test]
+fn test_invalid_input() {
+    // `usize` parsing accepts a leading `+`, but it isn't a recipe score.
+    assert_eq!(
+        solve("+5").unwrap_err().to_string(),
+        "invalid digit '+' in \"+5\""
+    );
+    assert!(solve("").is_err());
+    assert!(solve("5a").is_err());
+}
+
+#[test]
+fn test_seed() -> Result<()> {
+    let seed = parse_seed(&["3,7".to_owned()])?;
+    assert_eq!(seed, parse_seed(&["3".to_owned(), "7".to_owned()])?);
+    assert_eq!(solve_with(&seed, "9")?.0, "5158916779");
+    assert_eq!(solve_with(&seed, "2018")?.0, "5941429882");
+    assert_eq!(solve_with(&seed, "59414")?.1, 2018);
+
+    // Three recipes: elves on the two 1s make a 2, then both step onto a 2 and make a 4.
+    let board: Vec<usize> = Scores::new(&[1, 1, 2]).take(5).collect();
+    assert_eq!(board, [1, 1, 2, 2, 4]);
+
+    assert!(parse_seed(&["3".to_owned()]).is_err());
+    assert!(parse_seed(&["3,12".to_owned()]).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_two_digit_boundary() -> Result<()> {
+    // The board starts 3 7 1 0 1 0 1 2 4 5, where each "1 0" and "1 2" is a single append.
+    assert_eq!(solve("710")?.1, 1);
+    assert_eq!(solve("01012")?.1, 3);
+    assert_eq!(solve("1012")?.1, 4);
+    assert_eq!(solve("10124")?.1, 4);
+
+    let board: Vec<usize> = Scores::new(&[3, 7]).take(1000).collect();
+    for start in 0..100 {
+        let window = &board[start..start + 4];
+        let pattern: String = window.iter().map(|d| d.to_string()).collect();
+        let expected = board.windows(4).position(|w| w == window).unwrap();
+        assert_eq!(solve(&pattern)?.1, expected, "pattern {}", pattern);
+    }
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use d15::{run, Board, Outcome, Result};
+use std::io::Read;
+use std::time::Instant;
+
+const SIZE: usize = 96;
+
+// A walled square cave with scattered rock and units, from a fixed linear congruential sequence
+// so every run sees the same battle.
+fn cave() -> String {
+    let mut seed: u32 = 2018;
+    let mut cave = String::new();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let roll = (seed >> 16) % 100;
+            let c = if x == 0 || y == 0 || x == SIZE - 1 || y == SIZE - 1 || roll < 15 {
+                '#'
+            } else if roll < 18 {
+                'E'
+            } else if roll < 21 {
+                'G'
+            } else {
+                '.'
+            };
+            cave.push(c);
+        }
+        cave.push('\n');
+    }
+    cave
+}
+
+fn time_combat(name: &str, backtracking: bool) -> Result<Outcome> {
+    let mut board = Board::from_bytes(cave().as_bytes().bytes())?;
+    board.set_backtracking(backtracking);
+    let start = Instant::now();
+    let outcome = run(&mut board);
+    println!(
+        "{:>12}: {}x{} cave in {:?}, {} squares searched ({} full rounds, outcome {})",
+        name,
+        SIZE,
+        SIZE,
+        start.elapsed(),
+        board.searched(),
+        outcome.full_rounds,
+        outcome.answer()
+    );
+    Ok(outcome)
+}
+
+fn main() -> Result<()> {
+    let layered = time_combat("layered", false)?;
+    let backtracking = time_combat("backtracking", true)?;
+    assert_eq!(layered, backtracking, "the searches fought differently");
+    Ok(())
+}
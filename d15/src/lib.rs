@@ -0,0 +1,568 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read};
+use std::result;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+#[derive(Clone, PartialEq)]
+enum Tile {
+    Wall,
+    Open,
+    Unit,
+}
+
+// A side in the battle, named by the letter its units are drawn with. Units attack any unit of
+// another faction.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Faction(pub u8);
+
+pub const ELF: Faction = Faction(b'E');
+pub const GOBLIN: Faction = Faction(b'G');
+
+const DEFAULT_ATTACK: u32 = 3;
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Unit {
+    hp: u32,
+    kind: Faction,
+    id: usize,
+}
+
+enum Round {
+    Full,
+    CombatEnded,
+    // Nobody moved or attacked, so every later round would be the same.
+    Stalemate,
+}
+
+#[derive(Clone)]
+pub struct Board {
+    tiles: Vec<Tile>,
+    units: BTreeMap<usize, Unit>,
+    width: usize,
+    attacks: BTreeMap<Faction, u32>,
+    elf_deaths: u32,
+    // Whether units find their way with `bfs_step_backtracking` instead of `bfs_step`.
+    backtracking: bool,
+    // How many squares the searches have taken off their queues or layers so far.
+    searched: Cell<u64>,
+}
+
+impl Board {
+    pub fn from_bytes<R: Read>(bytes: io::Bytes<R>) -> Result<Board> {
+        let mut tiles = Vec::new();
+        let mut units = BTreeMap::new();
+        let mut width = 0;
+        for byte in bytes {
+            let byte = byte?;
+            if byte == b'\n' {
+                if width == 0 {
+                    width = tiles.len();
+                } else if tiles.len() % width != 0 {
+                    return Err("non-rectangular input".into());
+                }
+                continue;
+            }
+            let tile = match byte {
+                b'#' => Tile::Wall,
+                b'.' => Tile::Open,
+                b'A'..=b'Z' => {
+                    units.insert(
+                        tiles.len(),
+                        Unit {
+                            hp: 200,
+                            kind: Faction(byte),
+                            id: units.len(),
+                        },
+                    );
+                    Tile::Unit
+                }
+                _ => return Err("invalid byte".into()),
+            };
+            tiles.push(tile);
+        }
+        Ok(Board {
+            tiles,
+            units,
+            width,
+            attacks: BTreeMap::new(),
+            elf_deaths: 0,
+            backtracking: false,
+            searched: Cell::new(0),
+        })
+    }
+
+    fn neighbors(&self, pos: usize) -> impl Iterator<Item = usize> {
+        // Assumes board bordered by walls.
+        vec![pos - self.width, pos - 1, pos + 1, pos + self.width].into_iter()
+    }
+
+    fn open_neighbors(&self, pos: usize) -> impl Iterator<Item = usize> {
+        self.neighbors(pos)
+            .filter(|&pos| self.tiles[pos] == Tile::Open)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn enemy_neighbors(&self, pos: usize, kind: Faction) -> impl Iterator<Item = usize> {
+        self.neighbors(pos)
+            .filter(|pos| match self.units.get(pos) {
+                Some(unit) => unit.kind != kind,
+                None => false,
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    // Breadth-first search over open squares from `src` to the nearest of the sorted `dst`,
+    // returning the first step to take. Each layer is kept in order of first step, and the first
+    // steps are seeded in reading order, so every square is first reached via the earliest first
+    // step in reading order. Ties between equally near destinations also go by reading order.
+    fn bfs_step(&self, src: usize, dst: &[usize]) -> Option<usize> {
+        let mut visited = vec![false; self.tiles.len()];
+        visited[src] = true;
+        let mut layer: Vec<(usize, usize)> = Vec::new();
+        for step in self.open_neighbors(src) {
+            visited[step] = true;
+            layer.push((step, step));
+        }
+        while !layer.is_empty() {
+            self.searched.set(self.searched.get() + layer.len() as u64);
+            let nearest = layer
+                .iter()
+                .filter(|(pos, _)| dst.binary_search(pos).is_ok())
+                .min();
+            if let Some(&(_, step)) = nearest {
+                return Some(step);
+            }
+            let mut next_layer = Vec::new();
+            for &(pos, step) in &layer {
+                for neighbor in self.open_neighbors(pos) {
+                    if !visited[neighbor] {
+                        visited[neighbor] = true;
+                        next_layer.push((neighbor, step));
+                    }
+                }
+            }
+            layer = next_layer;
+        }
+        None
+    }
+
+    // The search `bfs_step` replaced, kept for the bench to compare against: it fills in the
+    // distance to every square up to the nearest destination, then walks back from the first of
+    // those in reading order to find every first step on a shortest path there.
+    fn bfs_step_backtracking(&self, src: usize, dst: &[usize]) -> Option<usize> {
+        let mut distances = vec![usize::MAX; self.tiles.len()];
+        let mut max_distance = usize::MAX;
+        let mut horizon = VecDeque::new();
+        horizon.push_back((0, src));
+        while let Some((distance, pos)) = horizon.pop_front() {
+            self.searched.set(self.searched.get() + 1);
+            if distance > max_distance {
+                break;
+            }
+            if distance >= distances[pos] {
+                continue;
+            } else {
+                distances[pos] = distance;
+            }
+            if dst.contains(&pos) {
+                max_distance = distance;
+            }
+            for neighbor in self.open_neighbors(pos) {
+                horizon.push_back((distance + 1, neighbor));
+            }
+        }
+        if max_distance == usize::MAX {
+            return None;
+        }
+
+        let position = dst
+            .iter()
+            .cloned()
+            .filter(|&d| distances[d] == max_distance)
+            .min()
+            .unwrap();
+        let mut positions = vec![position];
+        let mut distance = max_distance;
+        while distance > 1 {
+            distance -= 1;
+            positions = positions
+                .into_iter()
+                .flat_map(|p| self.open_neighbors(p))
+                .filter(|&p| distances[p] == distance)
+                .collect();
+            positions.sort();
+            positions.dedup();
+        }
+        Some(positions[0])
+    }
+
+    // Makes units find their way with the search `bfs_step` replaced, to time one against the
+    // other.
+    pub fn set_backtracking(&mut self, backtracking: bool) {
+        self.backtracking = backtracking;
+    }
+
+    // How many squares units have searched to find their way so far.
+    pub fn searched(&self) -> u64 {
+        self.searched.get()
+    }
+
+    // Sets the attack power of every unit in `faction`; factions default to 3.
+    pub fn set_attack(&mut self, faction: Faction, attack: u32) {
+        self.attacks.insert(faction, attack);
+    }
+
+    fn attack_for(&self, unit: &Unit) -> u32 {
+        *self.attacks.get(&unit.kind).unwrap_or(&DEFAULT_ATTACK)
+    }
+
+    fn next_round(&mut self) -> Round {
+        let mut acted = false;
+        let units: Vec<_> = self
+            .units
+            .keys()
+            .cloned()
+            .map(|p| (p, self.units[&p].id))
+            .collect();
+        for (mut pos, id) in units {
+            let unit = self.units.get(&pos);
+            if unit.map(|u| u.id != id).unwrap_or(true) {
+                continue;
+            }
+            let unit = unit.unwrap();
+
+            let targets: Vec<_> = self
+                .units
+                .iter()
+                .filter(|(_, target)| target.kind != unit.kind)
+                .collect();
+            if targets.is_empty() {
+                return Round::CombatEnded;
+            }
+
+            let mut targets: Vec<_> = targets
+                .iter()
+                .flat_map(|&(&pos, _)| self.neighbors(pos))
+                .filter(|&pos| self.tiles[pos] == Tile::Open)
+                .collect();
+            targets.sort();
+            targets.dedup();
+
+            if self.enemy_neighbors(pos, unit.kind).next().is_none() {
+                if targets.is_empty() {
+                    continue;
+                }
+                let step = if self.backtracking {
+                    self.bfs_step_backtracking(pos, &targets)
+                } else {
+                    self.bfs_step(pos, &targets)
+                };
+                if let Some(next_pos) = step {
+                    self.tiles.swap(pos, next_pos);
+                    let unit = self.units.remove(&pos).unwrap();
+                    self.units.insert(next_pos, unit);
+                    pos = next_pos;
+                    acted = true;
+                }
+            }
+
+            let unit = &self.units[&pos];
+            let enemy = self
+                .enemy_neighbors(pos, unit.kind)
+                .map(|pos| (&self.units[&pos], pos))
+                .min();
+            if let Some((enemy, enemy_pos)) = enemy {
+                acted = true;
+                let attack = self.attack_for(unit);
+                if enemy.hp <= attack {
+                    if enemy.kind == ELF {
+                        self.elf_deaths += 1;
+                    }
+                    self.tiles[enemy_pos] = Tile::Open;
+                    self.units.remove(&enemy_pos);
+                } else {
+                    self.units.get_mut(&enemy_pos).unwrap().hp -= attack;
+                }
+            }
+        }
+        if acted {
+            Round::Full
+        } else {
+            Round::Stalemate
+        }
+    }
+
+    fn remaining_hp(&self) -> u32 {
+        self.units.values().map(|unit| unit.hp).sum()
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut units = Vec::new();
+        for (i, tile) in self.tiles.iter().enumerate() {
+            let c = match tile {
+                Tile::Wall => '#',
+                Tile::Open => '.',
+                Tile::Unit => {
+                    let Unit { kind, hp, .. } = &self.units[&i];
+                    let c = kind.0 as char;
+                    units.push((c, hp));
+                    c
+                }
+            };
+            write!(f, "{}", c)?;
+            if (i + 1) % self.width == 0 {
+                if !units.is_empty() {
+                    let units_str = units
+                        .iter()
+                        .map(|(c, hp)| format!("{}({})", c, hp))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    write!(f, "   {}", units_str)?;
+                    units.clear();
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Outcome {
+    pub full_rounds: usize,
+    pub remaining_hp: u32,
+    pub elf_deaths: u32,
+    // Both sides survive but no unit can reach an enemy. The stalled round isn't counted.
+    pub stalemate: bool,
+}
+
+impl Outcome {
+    pub fn answer(&self) -> usize {
+        self.full_rounds * self.remaining_hp as usize
+    }
+}
+
+impl Board {
+    fn outcome(&self, full_rounds: usize, stalemate: bool) -> Outcome {
+        Outcome {
+            full_rounds,
+            remaining_hp: self.remaining_hp(),
+            elf_deaths: self.elf_deaths,
+            stalemate,
+        }
+    }
+}
+
+// Plays out combat until one side is left or a stalemate, calling `on_round` with the number of
+// completed rounds before the first round and after each full round.
+pub fn run_with<F>(board: &mut Board, mut on_round: F) -> Outcome
+where
+    F: FnMut(usize, &Board),
+{
+    let mut full_rounds = 0;
+    on_round(full_rounds, board);
+    loop {
+        match board.next_round() {
+            Round::Full => {
+                full_rounds += 1;
+                on_round(full_rounds, board);
+            }
+            Round::CombatEnded => return board.outcome(full_rounds, false),
+            Round::Stalemate => return board.outcome(full_rounds, true),
+        }
+    }
+}
+
+pub fn run(board: &mut Board) -> Outcome {
+    run_with(board, |_, _| {})
+}
+
+// Plays out combat with the elves at `attack`, returning the outcome unless an elf dies.
+fn outcome_without_elf_death(board: &Board, attack: u32) -> Option<Outcome> {
+    let mut board = board.clone();
+    board.set_attack(ELF, attack);
+    let mut full_rounds = 0;
+    loop {
+        let round = board.next_round();
+        if board.elf_deaths > 0 {
+            return None;
+        }
+        match round {
+            Round::Full => full_rounds += 1,
+            Round::CombatEnded => return Some(board.outcome(full_rounds, false)),
+            Round::Stalemate => return Some(board.outcome(full_rounds, true)),
+        }
+    }
+}
+
+// Finds the lowest elf attack power at which no elf dies, and the outcome of that combat. Raising
+// the attack only helps the elves, so this doubles the attack until the elves survive and then
+// bisects between the last failure and that success.
+pub fn min_attack_no_elf_death(board: &Board) -> (u32, Outcome) {
+    let mut failure = 3;
+    let mut success = 4;
+    let mut outcome = loop {
+        match outcome_without_elf_death(board, success) {
+            Some(outcome) => break outcome,
+            None => {
+                failure = success;
+                success *= 2;
+            }
+        }
+    };
+    while success - failure > 1 {
+        let attack = failure + (success - failure) / 2;
+        match outcome_without_elf_death(board, attack) {
+            Some(attack_outcome) => {
+                success = attack;
+                outcome = attack_outcome;
+            }
+            None => failure = attack,
+        }
+    }
+    (success, outcome)
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = "#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######
+";
+
+#[cfg(test)]
+fn parse(s: &str) -> Result<Board> {
+    Board::from_bytes(s.as_bytes().bytes())
+}
+
+#[test]
+fn test_run() -> Result<()> {
+    let outcome = run(&mut parse(EXAMPLE)?);
+    assert_eq!(
+        (
+            outcome.full_rounds,
+            outcome.remaining_hp,
+            outcome.elf_deaths
+        ),
+        (47, 590, 2)
+    );
+    assert_eq!(outcome.answer(), 27730);
+
+    let examples = [
+        (
+            "#######\n#G..#E#\n#E#E.E#\n#G.##.#\n#...#E#\n#...E.#\n#######\n",
+            37,
+            982,
+        ),
+        (
+            "#######\n#E..EG#\n#.#G.E#\n#E.##E#\n#G..#.#\n#..E#.#\n#######\n",
+            46,
+            859,
+        ),
+        (
+            "#######\n#E.G#.#\n#.#G..#\n#G.#.G#\n#G..#.#\n#...E.#\n#######\n",
+            35,
+            793,
+        ),
+        (
+            "#######\n#.E...#\n#.#..G#\n#.###.#\n#E#G#G#\n#...#G#\n#######\n",
+            54,
+            536,
+        ),
+    ];
+    for &(input, full_rounds, remaining_hp) in &examples {
+        let outcome = run(&mut parse(input)?);
+        assert_eq!(
+            (outcome.full_rounds, outcome.remaining_hp),
+            (full_rounds, remaining_hp)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_bfs_step() -> Result<()> {
+    // The puzzle's movement example: of the two nearest in-range squares the elf picks the first
+    // in reading order, then steps right rather than down.
+    let board = parse("#######\n#.E...#\n#.....#\n#...G.#\n#######\n")?;
+    // Positions are y * 7 + x: the elf is at 9, and the in-range squares are 18, 24 and 26.
+    assert_eq!(board.bfs_step(9, &[18, 24, 26]), Some(10));
+    assert_eq!(board.bfs_step(9, &[16]), Some(16));
+
+    let board = parse("#######\n#E.#.G#\n#######\n")?;
+    assert_eq!(board.bfs_step(8, &[11]), None);
+    assert_eq!(board.bfs_step_backtracking(8, &[11]), None);
+
+    // Both searches agree from every open square of a cave with several ways round.
+    let board = parse("#########\n#.......#\n#.#.##..#\n#...#.#.#\n#.#...#.#\n#########\n")?;
+    let open: Vec<_> = (0..board.tiles.len())
+        .filter(|&pos| board.tiles[pos] == Tile::Open)
+        .collect();
+    // Units only search when they aren't already in range, so the source is never a destination.
+    for &src in &open {
+        for dst in open.chunks(3).filter(|dst| !dst.contains(&src)) {
+            assert_eq!(
+                board.bfs_step(src, dst),
+                board.bfs_step_backtracking(src, dst),
+                "from {} to {:?}",
+                src,
+                dst
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_three_factions() -> Result<()> {
+    // With each faction in the middle in turn, the middle unit is attacked by both neighbors and
+    // hits the first of them in the tie-break order, so between the three boards every faction
+    // attacks each of the other two.
+    let cases = [
+        ("#####\n#EGX#\n#####\n", "#EGX#   E(197), G(194), X(200)"),
+        ("#####\n#GEX#\n#####\n", "#GEX#   G(197), E(194), X(200)"),
+        ("#####\n#EXG#\n#####\n", "#EXG#   E(197), X(194), G(200)"),
+    ];
+    for &(input, after_round) in &cases {
+        let mut board = parse(input)?;
+        assert!(matches!(board.next_round(), Round::Full));
+        assert_eq!(board.to_string().lines().nth(1), Some(after_round));
+    }
+
+    let mut board = parse("#####\n#EGX#\n#####\n")?;
+    board.set_attack(Faction(b'X'), 10);
+    let outcome = run(&mut board);
+    assert_eq!(outcome.elf_deaths, 1);
+    assert_eq!(board.units.len(), 1);
+    assert_eq!(board.units.values().next().unwrap().kind, Faction(b'X'));
+    Ok(())
+}
+
+#[test]
+fn test_stalemate() -> Result<()> {
+    let outcome = run(&mut parse("#######\n#E.#.G#\n#######\n")?);
+    assert!(outcome.stalemate);
+    assert_eq!((outcome.full_rounds, outcome.remaining_hp), (0, 400));
+
+    assert!(!run(&mut parse(EXAMPLE)?).stalemate);
+    Ok(())
+}
+
+#[test]
+fn test_min_attack_no_elf_death() -> Result<()> {
+    let (attack, outcome) = min_attack_no_elf_death(&parse(EXAMPLE)?);
+    assert_eq!((attack, outcome.answer()), (15, 4988));
+    assert_eq!(outcome.elf_deaths, 0);
+    Ok(())
+}
@@ -1,51 +1,14 @@
 use failure::{bail, ensure, format_err, Error};
+use std::env;
 use std::io::{self, Read};
 use std::result;
 use std::str::FromStr;
+use vm::{Instruction, OpCode};
 
 type Result<T> = result::Result<T, Error>;
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum OpCode {
-    addr,
-    addi,
-    mulr,
-    muli,
-    banr,
-    bani,
-    borr,
-    bori,
-    setr,
-    seti,
-    gtir,
-    gtri,
-    gtrr,
-    eqir,
-    eqri,
-    eqrr,
-}
-
-impl OpCode {
-    pub fn variants() -> impl Iterator<Item = OpCode> {
-        use OpCode::*;
-        static OPCODES: [OpCode; 16] = [
-            addr, addi, mulr, muli, banr, bani, borr, bori, setr, seti, gtir, gtri, gtrr, eqir,
-            eqri, eqrr,
-        ];
-        OPCODES.into_iter().cloned()
-    }
-}
-
-struct Instruction {
-    opcode: OpCode,
-    in1: u32,
-    in2: u32,
-    out: u32,
-}
-
 struct Machine {
-    registers: [u32; 4],
+    registers: [u64; 4],
 }
 
 impl Machine {
@@ -53,122 +16,114 @@ impl Machine {
         Machine { registers: [0; 4] }
     }
 
-    fn reg(&self, r: u32) -> u32 {
+    fn reg(&self, r: u64) -> u64 {
         self.registers[r as usize]
     }
 
-    fn execute(&mut self, instruction: &Instruction) {
-        use OpCode::*;
-        let &Instruction {
-            opcode,
-            in1: a,
-            in2: b,
-            out,
-        } = instruction;
-        self.registers[out as usize] = match opcode {
-            addr => self.reg(a) + self.reg(b),
-            addi => self.reg(a) + b,
-            mulr => self.reg(a) * self.reg(b),
-            muli => self.reg(a) * b,
-            banr => self.reg(a) & self.reg(b),
-            bani => self.reg(a) & b,
-            borr => self.reg(a) | self.reg(b),
-            bori => self.reg(a) | b,
-            setr => self.reg(a),
-            seti => a,
-            gtir => {
-                if a > self.reg(b) {
-                    1
-                } else {
-                    0
-                }
-            }
-            gtri => {
-                if self.reg(a) > b {
-                    1
-                } else {
-                    0
-                }
-            }
-            gtrr => {
-                if self.reg(a) > self.reg(b) {
-                    1
-                } else {
-                    0
-                }
-            }
-            eqir => {
-                if a == self.reg(b) {
-                    1
-                } else {
-                    0
-                }
-            }
-            eqri => {
-                if self.reg(a) == b {
-                    1
-                } else {
-                    0
-                }
-            }
-            eqrr => {
-                if self.reg(a) == self.reg(b) {
-                    1
-                } else {
-                    0
-                }
-            }
-        };
+    fn execute(&mut self, instruction: &Instruction<u64>) {
+        vm::execute(&mut self.registers, instruction);
     }
+
+    fn try_execute(&mut self, instruction: &Instruction<u64>) -> Result<()> {
+        Ok(vm::try_execute(&mut self.registers, instruction)?)
+    }
+
+    fn checked_execute(&mut self, instruction: &Instruction<u64>) -> Result<()> {
+        Ok(vm::checked_execute(&mut self.registers, instruction)?)
+    }
+}
+
+#[test]
+fn test_checked_execute() -> Result<()> {
+    let mut machine = Machine::new();
+    machine.registers = [1 << 40, 0, 0, 0];
+    let instruction = "muli 0 16777216 1".parse()?;
+    let err = machine.checked_execute(&instruction).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "arithmetic overflow in `muli 0 16777216 1`"
+    );
+    assert_eq!(machine.registers, [1 << 40, 0, 0, 0]);
+
+    machine.execute(&instruction);
+    assert_eq!(machine.reg(1), 0);
+    machine.checked_execute(&"muli 0 4 1".parse()?)?;
+    assert_eq!(machine.reg(1), 1 << 42);
+    Ok(())
+}
+
+#[test]
+fn test_try_execute() -> Result<()> {
+    let mut machine = Machine::new();
+    machine.registers = [1, 2, 3, 4];
+    for (instruction, register) in &[("addr 9 1 2", 9), ("addr 1 9 2", 9), ("seti 9 9 4", 4)] {
+        let err = machine.try_execute(&instruction.parse()?).unwrap_err();
+        let expected = format!(
+            "register {} out of range for 4 registers in `{}`",
+            register, instruction
+        );
+        assert_eq!(err.to_string(), expected);
+        assert!(machine.checked_execute(&instruction.parse()?).is_err());
+    }
+    assert_eq!(machine.registers, [1, 2, 3, 4]);
+
+    // Immediate operands aren't registers, however large.
+    machine.try_execute(&"addi 1 9 0".parse()?)?;
+    machine.try_execute(&"gtir 9 2 1".parse()?)?;
+    assert_eq!(machine.registers, [11, 1, 3, 4]);
+    Ok(())
 }
 
 struct BlackboxInput {
-    before: [u32; 4],
-    instruction: [u32; 4],
-    after: [u32; 4],
+    before: [u64; 4],
+    instruction: [u64; 4],
+    after: [u64; 4],
+}
+
+// Parses exactly four whitespace- or comma-separated numbers.
+fn parse_four(s: &str, separator: char, what: &str) -> Result<[u64; 4]> {
+    let values: Vec<u64> = s
+        .split(separator)
+        .map(str::trim)
+        .map(|v| {
+            v.parse()
+                .map_err(|_| format_err!("invalid {} value `{}`", what, v))
+        })
+        .collect::<Result<_>>()?;
+    match values.as_slice() {
+        &[a, b, c, d] => Ok([a, b, c, d]),
+        _ => bail!("expected 4 {} values, found {}", what, values.len()),
+    }
+}
+
+// Parses a line like `Before: [3, 2, 1, 1]`.
+fn parse_registers(line: &str, prefix: &str, what: &str) -> Result<[u64; 4]> {
+    let rest = line
+        .strip_prefix(prefix)
+        .ok_or_else(|| format_err!("expected `{}`", prefix))?
+        .trim();
+    let inner = rest
+        .strip_prefix('[')
+        .and_then(|r| r.strip_suffix(']'))
+        .ok_or_else(|| format_err!("expected bracketed {} registers", what))?;
+    parse_four(inner, ',', what)
 }
 
 impl FromStr for BlackboxInput {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<BlackboxInput> {
-        let lines: Vec<_> = s.split("\n").collect();
-        ensure!(lines.len() == 3, "paragraph");
-
+        let lines: Vec<_> = s.trim_end().lines().collect();
         ensure!(
-            lines[0].starts_with("Before: [") && lines[0].ends_with("]"),
-            "before"
+            lines.len() == 3,
+            "expected 3 lines in sample, found {}",
+            lines.len()
         );
-        let before: Vec<u32> = lines[0][9..lines[0].len() - 1]
-            .split(", ")
-            .map(|s| s.parse())
-            .collect::<result::Result<_, _>>()?;
-        let before = match before.as_slice() {
-            &[a, b, c, d] => [a, b, c, d],
-            _ => bail!("before"),
-        };
-
-        let instruction: Vec<u32> = lines[1]
-            .split(" ")
-            .map(|s| s.parse())
-            .collect::<result::Result<_, _>>()?;
-        let instruction = match instruction.as_slice() {
-            &[a, b, c, d] => [a, b, c, d],
-            _ => bail!("instruction"),
-        };
+        let at_line = |n: usize| move |e: Error| format_err!("line {}: {}", n, e);
 
-        ensure!(
-            lines[2].starts_with("After:  [") && lines[2].ends_with("]"),
-            "after"
-        );
-        let after: Vec<u32> = lines[2][9..lines[2].len() - 1]
-            .split(", ")
-            .map(|s| s.parse())
-            .collect::<result::Result<_, _>>()?;
-        let after = match after.as_slice() {
-            &[a, b, c, d] => [a, b, c, d],
-            _ => bail!("after"),
-        };
+        let before = parse_registers(lines[0], "Before:", "before").map_err(at_line(1))?;
+        let instruction = parse_four(lines[1].trim(), ' ', "instruction").map_err(at_line(2))?;
+        let after = parse_registers(lines[2], "After:", "after").map_err(at_line(3))?;
 
         Ok(BlackboxInput {
             before,
@@ -178,83 +133,208 @@ impl FromStr for BlackboxInput {
     }
 }
 
-fn main() -> Result<()> {
-    let mut input = String::new();
-    io::stdin().read_to_string(&mut input)?;
-    let mut input = input.split("\n\n\n\n");
-    let part1 = input.next().ok_or_else(|| format_err!("bad input"))?;
-    let part2 = input.next().ok_or_else(|| format_err!("bad input"))?;
+#[test]
+fn test_parse_blackbox_input() -> Result<()> {
+    let sample: BlackboxInput = "Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n".parse()?;
+    assert_eq!(sample.before, [3, 2, 1, 1]);
+    assert_eq!(sample.instruction, [9, 2, 1, 2]);
+    assert_eq!(sample.after, [3, 2, 2, 1]);
 
-    let part1: Vec<BlackboxInput> = part1
-        .split("\n\n")
-        .map(|s| s.parse())
-        .collect::<result::Result<_, _>>()?;
+    let malformed = [
+        (
+            "Before: [3, 2, 1, 1]\n9 2 1 2",
+            "expected 3 lines in sample, found 2",
+        ),
+        (
+            "Befor: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]",
+            "line 1: expected `Before:`",
+        ),
+        (
+            "Before: [3, 2, 1\n9 2 1 2\nAfter:  [3, 2, 2, 1]",
+            "line 1: expected bracketed before registers",
+        ),
+        (
+            "Before: [3, 2, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]",
+            "line 1: expected 4 before values, found 3",
+        ),
+        (
+            "Before: [3, 2, 1, 1]\n9 2 x 2\nAfter:  [3, 2, 2, 1]",
+            "line 2: invalid instruction value `x`",
+        ),
+        (
+            "Before: [3, 2, 1, 1]\n9 2 1 2\nAfter: []",
+            "line 3: invalid after value ``",
+        ),
+        (
+            "Before: [3, 2, 1, 1]\n9 2 1 2\n[3, 2, 2, 1]",
+            "line 3: expected `After:`",
+        ),
+    ];
+    for &(paragraph, err) in &malformed {
+        let result = paragraph.parse::<BlackboxInput>();
+        assert_eq!(result.err().expect(paragraph).to_string(), err);
+    }
+    Ok(())
+}
 
+fn matching_opcodes(sample: &BlackboxInput) -> Vec<OpCode> {
+    let mut machine = Machine::new();
+    let mut matching = Vec::new();
+    let in_range = sample.instruction[1..].iter().all(|&r| r < 4);
+    for opcode in OpCode::variants() {
+        machine.registers = sample.before;
+        let instruction = Instruction {
+            opcode,
+            in1: sample.instruction[1],
+            in2: sample.instruction[2],
+            out: sample.instruction[3],
+        };
+        // An opcode that would read or write a missing register can't be the one sampled. That
+        // only needs checking when an operand is out of range.
+        let valid = if in_range {
+            machine.execute(&instruction);
+            true
+        } else {
+            machine.try_execute(&instruction).is_ok()
+        };
+        if valid && machine.registers == sample.after {
+            matching.push(opcode);
+        }
+    }
+    matching
+}
+
+// Narrows each opcode number to the opcodes consistent with every sample, then repeatedly rules
+// out opcodes that another number has been resolved to.
+fn learn_mapping(samples: &[BlackboxInput]) -> Result<[OpCode; 16]> {
     let mut opcode_candidates: [Vec<OpCode>; 16] = Default::default();
     for candidates in &mut opcode_candidates {
         candidates.extend(OpCode::variants());
     }
-    let mut behaves_like_3 = 0;
-    let mut machine = Machine::new();
-    for BlackboxInput {
-        before,
-        instruction,
-        after,
-    } in part1
-    {
-        let mut matching_opcodes = Vec::new();
-        for opcode in OpCode::variants() {
+    for sample in samples {
+        let number = sample.instruction[0] as usize;
+        ensure!(number < 16, "opcode number {} out of range", number);
+        let matching = matching_opcodes(sample);
+        opcode_candidates[number].retain(|c| matching.contains(c));
+    }
+
+    while opcode_candidates.iter().any(|c| c.len() > 1) {
+        let mut resolved = Vec::new();
+        for candidates in &opcode_candidates {
+            if candidates.len() == 1 {
+                resolved.extend_from_slice(candidates);
+            }
+        }
+        let mut progress = false;
+        for candidates in &mut opcode_candidates {
+            if candidates.len() == 1 {
+                continue;
+            }
+            let before = candidates.len();
+            candidates.retain(|c| !resolved.contains(c));
+            progress |= candidates.len() < before;
+        }
+        ensure!(progress, "samples don't determine a unique mapping");
+    }
+
+    let mut mapping = [OpCode::addr; 16];
+    for (number, candidates) in opcode_candidates.iter().enumerate() {
+        ensure!(
+            !candidates.is_empty(),
+            "no opcode matches number {}",
+            number
+        );
+        mapping[number] = candidates[0];
+    }
+    Ok(mapping)
+}
+
+#[test]
+fn test_learn_mapping() -> Result<()> {
+    // Number n stands for the opcode 5n places along, wrapping. Samples use small pseudo-random
+    // registers and operands, with enough of them per number to rule out all other opcodes.
+    let expected: Vec<OpCode> = (0..16)
+        .map(|n| OpCode::variants().nth(n * 5 % 16).unwrap())
+        .collect();
+    let mut seed = 7u64;
+    let mut next = |n: u64| {
+        seed = (seed * 1103515245 + 12345) % (1 << 31);
+        (seed >> 16) % n
+    };
+    let mut samples = Vec::new();
+    for (number, &opcode) in expected.iter().enumerate() {
+        for _ in 0..20 {
+            let before = [next(8), next(8), next(8), next(8)];
+            let instruction = [number as u64, next(4), next(4), next(4)];
+            let mut machine = Machine::new();
             machine.registers = before;
-            let instruction = Instruction {
+            machine.execute(&Instruction {
                 opcode,
                 in1: instruction[1],
                 in2: instruction[2],
                 out: instruction[3],
-            };
-            machine.execute(&instruction);
-            if machine.registers == after {
-                matching_opcodes.push(opcode);
-            }
+            });
+            samples.push(BlackboxInput {
+                before,
+                instruction,
+                after: machine.registers,
+            });
         }
-        opcode_candidates[instruction[0] as usize].retain(|c| matching_opcodes.contains(c));
-        if matching_opcodes.len() >= 3 {
-            behaves_like_3 += 1;
+    }
+    assert_eq!(learn_mapping(&samples)?.to_vec(), expected);
+    assert!(learn_mapping(&samples[..1]).is_err());
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut show_mapping = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--mapping" => show_mapping = true,
+            _ => bail!("unexpected argument {}", arg),
         }
     }
 
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let mut input = input.split("\n\n\n\n");
+    let part1 = input.next().ok_or_else(|| format_err!("bad input"))?;
+    let part2 = input.next().ok_or_else(|| format_err!("bad input"))?;
+
+    let part1: Vec<BlackboxInput> = part1
+        .split("\n\n")
+        .map(|s| s.parse())
+        .collect::<result::Result<_, _>>()?;
+
+    let behaves_like_3 = part1
+        .iter()
+        .filter(|sample| matching_opcodes(sample).len() >= 3)
+        .count();
     println!("{}", behaves_like_3);
 
-    while opcode_candidates.iter().any(|c| c.len() > 1) {
-        let mut resolved = Vec::new();
-        for candidates in &opcode_candidates {
-            if candidates.len() == 1 {
-                resolved.extend_from_slice(&candidates);
-            }
-        }
-        for i in 0..opcode_candidates.len() {
-            if opcode_candidates[i].len() == 1 {
-                continue;
-            }
-            opcode_candidates[i].retain(|c| !resolved.contains(&c));
+    let mapping = learn_mapping(&part1)?;
+    if show_mapping {
+        for (number, opcode) in mapping.iter().enumerate() {
+            println!("{} -> {}", number, opcode);
         }
     }
 
     let mut machine = Machine::new();
     for line in part2.lines() {
-        let instruction: Vec<u32> = line
+        let instruction: Vec<u64> = line
             .split(" ")
             .map(|s| s.parse())
             .collect::<result::Result<_, _>>()?;
         let instruction = match instruction.as_slice() {
-            &[opcode, in1, in2, out] => Instruction {
-                opcode: opcode_candidates[opcode as usize][0],
+            &[opcode, in1, in2, out] if opcode < 16 => Instruction {
+                opcode: mapping[opcode as usize],
                 in1,
                 in2,
                 out,
             },
             _ => bail!("instruction"),
         };
-        machine.execute(&instruction);
+        machine.checked_execute(&instruction)?;
     }
 
     println!("{}", machine.reg(0));
@@ -0,0 +1,470 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use std::result;
+use std::str::FromStr;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+pub struct Vein {
+    xmin: usize,
+    xmax: usize,
+    ymin: usize,
+    ymax: usize,
+}
+
+// Splits `axis=value` and checks the axis is `x` or `y`, returning whether it's `x`.
+fn parse_axis<'a>(s: &'a str, what: &str) -> Result<(bool, &'a str)> {
+    let (axis, value) = match s.find('=') {
+        Some(i) => (s[..i].trim(), s[i + 1..].trim()),
+        None => return Err(format!("expected `x=` or `y=` in {} `{}`", what, s).into()),
+    };
+    match axis {
+        "x" => Ok((true, value)),
+        "y" => Ok((false, value)),
+        _ => Err(format!("unknown axis `{}` in {} `{}`", axis, what, s).into()),
+    }
+}
+
+fn parse_coord(s: &str) -> Result<usize> {
+    s.parse()
+        .map_err(|_| format!("invalid coordinate `{}`", s).into())
+}
+
+impl FromStr for Vein {
+    type Err = Box<dyn Error>;
+
+    // Parses a line like `x=495, y=2..7` or `y=7, x=495..501`.
+    fn from_str(s: &str) -> Result<Vein> {
+        let (first, second) = match s.find(',') {
+            Some(i) => (s[..i].trim(), s[i + 1..].trim()),
+            None => return Err(format!("expected two comma-separated ranges in `{}`", s).into()),
+        };
+        let (xfirst, first) = parse_axis(first, "first range")?;
+        let (xsecond, second) = parse_axis(second, "second range")?;
+        if xfirst == xsecond {
+            return Err(format!("both ranges are along the same axis in `{}`", s).into());
+        }
+        let first = parse_coord(first)?;
+        let (secondmin, secondmax) = match second.find("..") {
+            Some(i) => (parse_coord(&second[..i])?, parse_coord(&second[i + 2..])?),
+            None => return Err(format!("expected `min..max` at `{}`", second).into()),
+        };
+        if secondmin > secondmax {
+            return Err(format!("range `{}` is backwards", second).into());
+        }
+        if xfirst {
+            Ok(Vein {
+                xmin: first,
+                xmax: first,
+                ymin: secondmin,
+                ymax: secondmax,
+            })
+        } else {
+            Ok(Vein {
+                xmin: secondmin,
+                xmax: secondmax,
+                ymin: first,
+                ymax: first,
+            })
+        }
+    }
+}
+
+#[test]
+fn test_parse_vein() -> Result<()> {
+    let vein: Vein = "x=495, y=2..7".parse()?;
+    assert_eq!(
+        (vein.xmin, vein.xmax, vein.ymin, vein.ymax),
+        (495, 495, 2, 7)
+    );
+    let vein: Vein = "y=7, x=495..501".parse()?;
+    assert_eq!(
+        (vein.xmin, vein.xmax, vein.ymin, vein.ymax),
+        (495, 501, 7, 7)
+    );
+
+    let malformed = [
+        (
+            "x=495 y=2..7",
+            "expected two comma-separated ranges in `x=495 y=2..7`",
+        ),
+        (
+            "x=495, x=2..7",
+            "both ranges are along the same axis in `x=495, x=2..7`",
+        ),
+        ("z=495, y=2..7", "unknown axis `z` in first range `z=495`"),
+        (
+            "x=495, y2..7",
+            "expected `x=` or `y=` in second range `y2..7`",
+        ),
+        ("x=495, y=2.7", "expected `min..max` at `2.7`"),
+        ("x=4a5, y=2..7", "invalid coordinate `4a5`"),
+        ("x=495, y=7..2", "range `7..2` is backwards"),
+    ];
+    for &(line, err) in &malformed {
+        assert_eq!(line.parse::<Vein>().err().expect(line).to_string(), err);
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Coord(pub usize, pub usize);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tile {
+    Clay,
+    Settled,
+    Passed,
+}
+
+// A row of water spreading sideways from the column at `x`.
+#[derive(Clone, Copy)]
+struct Row {
+    x: usize,
+    y: usize,
+    xleft: usize,
+    xright: usize,
+    bounded: bool,
+}
+
+// Work left for `World::spill`. The `Landed` steps check whether water that went over an edge
+// stayed there once the spill below it has run.
+#[derive(Clone, Copy)]
+enum Step {
+    Spill(Coord),
+    Fill(usize, usize),
+    Left(Row),
+    LeftLanded(Row),
+    Right(Row),
+    RightLanded(Row),
+}
+
+pub struct World {
+    tiles: HashMap<Coord, Tile>,
+    xmin: usize,
+    xmax: usize,
+    ymin: usize,
+    ymax: usize,
+}
+
+impl fmt::Display for World {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.tiles.is_empty() {
+            return Ok(());
+        }
+        for y in self.ymin..=self.ymax {
+            for x in self.xmin..=self.xmax {
+                let c = match self.tiles.get(&Coord(x, y)) {
+                    Some(Tile::Clay) => '#',
+                    Some(Tile::Settled) => '~',
+                    Some(Tile::Passed) => '|',
+                    None => '.',
+                };
+                write!(f, "{}", c)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Draws the world as a binary PPM, one pixel per tile, in the same bounds as `Display`.
+pub fn render_ppm(world: &World) -> Vec<u8> {
+    let (width, height) = if world.tiles.is_empty() {
+        (0, 0)
+    } else {
+        (world.xmax - world.xmin + 1, world.ymax - world.ymin + 1)
+    };
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for y in 0..height {
+        for x in 0..width {
+            let color = match world.tiles.get(&Coord(world.xmin + x, world.ymin + y)) {
+                Some(Tile::Clay) => [139, 90, 43],
+                Some(Tile::Settled) => [0, 0, 255],
+                Some(Tile::Passed) => [135, 206, 250],
+                None => [255, 255, 255],
+            };
+            out.extend_from_slice(&color);
+        }
+    }
+    out
+}
+
+impl World {
+    pub fn from_veins(veins: &[Vein]) -> Self {
+        let mut world = World::new();
+        for vein in veins {
+            for x in vein.xmin..=vein.xmax {
+                for y in vein.ymin..=vein.ymax {
+                    world.set(Coord(x, y), Tile::Clay);
+                }
+            }
+        }
+        world
+    }
+
+    fn new() -> Self {
+        World {
+            tiles: HashMap::new(),
+            xmin: usize::MAX,
+            xmax: usize::MIN,
+            ymin: usize::MAX,
+            ymax: usize::MIN,
+        }
+    }
+
+    fn flows(&mut self, coord: Coord) -> bool {
+        match self.tiles.get(&coord) {
+            Some(Tile::Passed) => true,
+            None => {
+                self.set(coord, Tile::Passed);
+                true
+            }
+            Some(Tile::Clay) | Some(Tile::Settled) => false,
+        }
+    }
+
+    fn set(&mut self, coord: Coord, tile: Tile) {
+        if coord.0 < self.xmin {
+            self.xmin = coord.0;
+        }
+        if coord.0 > self.xmax {
+            self.xmax = coord.0;
+        }
+        if coord.1 < self.ymin {
+            self.ymin = coord.1;
+        }
+        if coord.1 > self.ymax {
+            self.ymax = coord.1;
+        }
+        self.tiles.insert(coord, tile);
+    }
+
+    // Follows water down from `from`, filling each basin it lands in row by row from the bottom.
+    // Overflowing an edge starts a new spill below it, which finishes before the row carries on,
+    // so pending work is kept on a stack rather than recursing.
+    fn spill(&mut self, from: Coord, visited: &mut HashSet<Coord>) {
+        let mut stack = vec![Step::Spill(from)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Spill(from) => {
+                    if !visited.insert(from) {
+                        continue;
+                    }
+                    let Coord(x, mut y) = from;
+                    if y < self.ymin {
+                        y = self.ymin;
+                    }
+                    self.set(Coord(x, y), Tile::Passed);
+                    // flow down
+                    let mut escaped = false;
+                    while self.flows(Coord(x, y + 1)) {
+                        y += 1;
+                        if y >= self.ymax {
+                            escaped = true;
+                            break;
+                        }
+                    }
+                    if !escaped {
+                        stack.push(Step::Fill(x, y));
+                    }
+                }
+                // flow back
+                Step::Fill(x, y) => {
+                    if y >= self.ymin && !self.flows(Coord(x, y + 1)) {
+                        let row = Row {
+                            x,
+                            y,
+                            xleft: x,
+                            xright: x,
+                            bounded: true,
+                        };
+                        stack.push(Step::Left(row));
+                    }
+                }
+                // flow left
+                Step::Left(mut row) => {
+                    if self.flows(Coord(row.xleft - 1, row.y)) {
+                        row.xleft -= 1;
+                        let below = Coord(row.xleft, row.y + 1);
+                        stack.push(Step::LeftLanded(row));
+                        if self.flows(below) {
+                            stack.push(Step::Spill(below));
+                        }
+                    } else {
+                        stack.push(Step::Right(row));
+                    }
+                }
+                Step::LeftLanded(mut row) => {
+                    if self.flows(Coord(row.xleft, row.y + 1)) {
+                        row.bounded = false;
+                        stack.push(Step::Right(row));
+                    } else {
+                        stack.push(Step::Left(row));
+                    }
+                }
+                // flow right
+                Step::Right(mut row) => {
+                    if self.flows(Coord(row.xright + 1, row.y)) {
+                        row.xright += 1;
+                        let below = Coord(row.xright, row.y + 1);
+                        stack.push(Step::RightLanded(row));
+                        if self.flows(below) {
+                            stack.push(Step::Spill(below));
+                        }
+                    } else {
+                        self.finish_row(row, &mut stack);
+                    }
+                }
+                Step::RightLanded(mut row) => {
+                    if self.flows(Coord(row.xright, row.y + 1)) {
+                        row.bounded = false;
+                        self.finish_row(row, &mut stack);
+                    } else {
+                        stack.push(Step::Right(row));
+                    }
+                }
+            }
+        }
+    }
+
+    // Spills from a spring, which has to be above all the clay.
+    pub fn pour(&mut self, spring: Coord) -> Result<()> {
+        if self.tiles.is_empty() {
+            return Err("no clay to pour onto".into());
+        }
+        if spring.1 >= self.ymin {
+            let msg = format!(
+                "spring at y={} isn't above the clay at y={}",
+                spring.1, self.ymin
+            );
+            return Err(msg.into());
+        }
+        self.spill(spring, &mut HashSet::new());
+        Ok(())
+    }
+
+    fn finish_row(&mut self, row: Row, stack: &mut Vec<Step>) {
+        if row.bounded {
+            for x in row.xleft..=row.xright {
+                self.set(Coord(x, row.y), Tile::Settled);
+            }
+        }
+        stack.push(Step::Fill(row.x, row.y - 1));
+    }
+
+    pub fn counts(&self) -> WaterCounts {
+        WaterCounts {
+            reachable: self.count_reachable(),
+            settled: self.count_settled(),
+        }
+    }
+
+    fn count_reachable(&self) -> usize {
+        self.tiles
+            .values()
+            .filter(|&&t| t == Tile::Settled || t == Tile::Passed)
+            .count()
+    }
+
+    fn count_settled(&self) -> usize {
+        self.tiles.values().filter(|&&t| t == Tile::Settled).count()
+    }
+}
+
+#[test]
+fn test_spill_deep_staircase() {
+    // Each ledge is one step down and right of the last, so the water runs off every ledge in
+    // turn before landing in a cup at the bottom. Spilling recursively overflowed the stack at
+    // around ten thousand steps.
+    let n = 100_000;
+    let mut world = World::new();
+    world.set(Coord(499, 1), Tile::Clay);
+    for k in 0..n {
+        world.set(Coord(500 + k, 2 + k), Tile::Clay);
+    }
+    for x in 495 + n..=505 + n {
+        world.set(Coord(x, n + 10), Tile::Clay);
+    }
+    for y in n + 5..n + 10 {
+        world.set(Coord(495 + n, y), Tile::Clay);
+        world.set(Coord(505 + n, y), Tile::Clay);
+    }
+    world.spill(Coord(500, 0), &mut HashSet::new());
+    assert_eq!(world.count_reachable(), 2 * n + 73);
+    assert_eq!(world.count_settled(), 45);
+}
+
+pub fn read_veins(input: impl BufRead) -> Result<Vec<Vein>> {
+    input.lines().map(|line| line?.parse()).collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct WaterCounts {
+    pub reachable: usize,
+    pub settled: usize,
+}
+
+// Pours water from a spring at the top of column `spring_x` and counts where it ends up.
+pub fn simulate(veins: &[Vein], spring_x: usize) -> Result<WaterCounts> {
+    let mut world = World::from_veins(veins);
+    world.pour(Coord(spring_x, 0))?;
+    Ok(world.counts())
+}
+
+#[test]
+fn test_simulate() -> Result<()> {
+    let example = "x=495, y=2..7
+y=7, x=495..501
+x=501, y=3..7
+x=498, y=2..4
+x=506, y=1..2
+x=498, y=10..13
+x=504, y=10..13
+y=13, x=498..504
+";
+    let veins = read_veins(example.as_bytes())?;
+    let expected = WaterCounts {
+        reachable: 57,
+        settled: 29,
+    };
+    assert_eq!(simulate(&veins, 500)?, expected);
+
+    // The same, moved 480 columns left along with its spring.
+    let shifted = "x=15, y=2..7
+y=7, x=15..21
+x=21, y=3..7
+x=18, y=2..4
+x=26, y=1..2
+x=18, y=10..13
+x=24, y=10..13
+y=13, x=18..24
+";
+    let veins = read_veins(shifted.as_bytes())?;
+    assert_eq!(simulate(&veins, 20)?, expected);
+
+    let veins = read_veins("y=0, x=10..30\n".as_bytes())?;
+    let err = simulate(&veins, 20).unwrap_err();
+    assert_eq!(err.to_string(), "spring at y=0 isn't above the clay at y=0");
+    Ok(())
+}
+
+#[test]
+fn test_render_ppm() -> Result<()> {
+    let veins = read_veins("x=3, y=2..4\ny=4, x=4..6\nx=7, y=3..4\nx=10, y=4..4\n".as_bytes())?;
+    let mut world = World::from_veins(&veins);
+    world.pour(Coord(5, 0))?;
+    assert_eq!(world.to_string(), "#|||||..\n#~~~#|..\n#####|.#\n");
+    let ppm = render_ppm(&world);
+    let header = b"P6\n8 3\n255\n";
+    assert_eq!(&ppm[..header.len()], header);
+    assert_eq!(ppm.len(), header.len() + 8 * 3 * 3);
+    let pixel = |x: usize, y: usize| &ppm[header.len() + (y * 8 + x) * 3..][..3];
+    assert_eq!(pixel(7, 2), [139, 90, 43]);
+    assert_eq!(pixel(2, 1), [0, 0, 255]);
+    assert_eq!(pixel(5, 2), [135, 206, 250]);
+    assert_eq!(pixel(6, 0), [255, 255, 255]);
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use d18::{Area, Result};
+use std::time::Instant;
+
+const SIZE: usize = 1000;
+const MINUTES: usize = 200;
+
+// A pseudo-random square of acres, a third of each kind.
+fn random_area() -> Result<Area> {
+    let mut seed = 1u64;
+    let mut input = Vec::with_capacity((SIZE + 1) * SIZE);
+    for _ in 0..SIZE {
+        for _ in 0..SIZE {
+            seed = (seed * 1103515245 + 12345) % (1 << 31);
+            input.push(b".|#"[(seed >> 16) as usize % 3]);
+        }
+        input.push(b'\n');
+    }
+    Area::from_bytes(&mut input.into_iter().map(Ok))
+}
+
+fn time_steps(name: &str, step: fn(&mut Area)) -> Result<String> {
+    let mut area = random_area()?;
+    let start = Instant::now();
+    for _ in 0..MINUTES {
+        step(&mut area);
+    }
+    println!(
+        "{:>8}: {:?} (resource value {})",
+        name,
+        start.elapsed(),
+        area.resource_value()
+    );
+    Ok(area.to_string())
+}
+
+fn main() -> Result<()> {
+    let serial = time_steps("serial", Area::step)?;
+    let parallel = time_steps("parallel", Area::step_parallel)?;
+    assert!(
+        serial == parallel,
+        "parallel steps diverged from serial ones"
+    );
+    Ok(())
+}
@@ -0,0 +1,361 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::mem;
+use std::result;
+
+use rayon::prelude::*;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Tile {
+    Open,
+    Tree,
+    Lumber,
+}
+
+// How many neighbouring acres of each kind it takes for an acre to change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rules {
+    // Open ground fills with trees next to at least this many trees.
+    pub trees_to_grow: usize,
+    // Trees become a lumberyard next to at least this many lumberyards.
+    pub lumber_to_build: usize,
+    // A lumberyard stays one only next to at least this many lumberyards and trees.
+    pub lumber_to_keep: usize,
+    pub trees_to_keep: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            trees_to_grow: 3,
+            lumber_to_build: 3,
+            lumber_to_keep: 1,
+            trees_to_keep: 1,
+        }
+    }
+}
+
+pub struct Area {
+    width: usize,
+    rules: Rules,
+    tiles: Vec<Tile>,
+    scratch: Vec<Tile>,
+    time: usize,
+    history: HashMap<u64, usize>,
+    periodicity: Option<usize>,
+    // Whether the last step left every tile as it was, so no later one will change anything.
+    unchanging: bool,
+    // The resource value after each minute so far, starting from the initial state.
+    values: Vec<usize>,
+}
+
+impl Area {
+    pub fn from_bytes(bytes: &mut impl Iterator<Item = io::Result<u8>>) -> Result<Self> {
+        let mut width = None;
+        let mut tiles = Vec::new();
+        for c in bytes {
+            match c? {
+                b'.' => tiles.push(Tile::Open),
+                b'|' => tiles.push(Tile::Tree),
+                b'#' => tiles.push(Tile::Lumber),
+                b'\n' => {
+                    if let Some(width) = width {
+                        if tiles.len() % width != 0 {
+                            return Err("parse failed".into());
+                        }
+                    } else {
+                        width = Some(tiles.len());
+                    }
+                }
+                _ => return Err("parse failed".into()),
+            }
+        }
+        match width {
+            Some(width) if tiles.len() % width == 0 => {
+                let scratch = vec![Tile::Open; tiles.len()];
+                let mut area = Area {
+                    width,
+                    rules: Rules::default(),
+                    tiles,
+                    scratch,
+                    time: 0,
+                    history: HashMap::new(),
+                    periodicity: None,
+                    unchanging: false,
+                    values: Vec::new(),
+                };
+                area.values.push(area.resource_value());
+                Ok(area)
+            }
+            _ => Err("parse failed".into()),
+        }
+    }
+
+    // Applies `rules` from the next step on. States seen under the old rules no longer say
+    // anything about repeats.
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        self.history.clear();
+        self.periodicity = None;
+        self.unchanging = false;
+    }
+
+    fn adjacencies(&self, i: usize) -> (usize, usize, usize) {
+        let offsets = [self.width - 1, self.width, 1, self.width + 1];
+        let (mut open, mut tree, mut lumber) = (0, 0, 0);
+        // assumes width > 1
+        let (neg_offsets, pos_offsets) = match i % self.width {
+            0 => (&offsets[0..2], &offsets[1..4]),
+            x if x == self.width - 1 => (&offsets[1..4], &offsets[0..2]),
+            _ => (&offsets[..], &offsets[..]),
+        };
+        for &offset in neg_offsets {
+            if i >= offset {
+                match self.tiles[i - offset] {
+                    Tile::Open => open += 1,
+                    Tile::Tree => tree += 1,
+                    Tile::Lumber => lumber += 1,
+                }
+            }
+        }
+        for &offset in pos_offsets {
+            if i + offset < self.tiles.len() {
+                match self.tiles[i + offset] {
+                    Tile::Open => open += 1,
+                    Tile::Tree => tree += 1,
+                    Tile::Lumber => lumber += 1,
+                }
+            }
+        }
+        (open, tree, lumber)
+    }
+
+    fn next_tile(&self, i: usize) -> Tile {
+        let (_open, tree, lumber) = self.adjacencies(i);
+        let rules = &self.rules;
+        match self.tiles[i] {
+            Tile::Open => {
+                if tree >= rules.trees_to_grow {
+                    Tile::Tree
+                } else {
+                    Tile::Open
+                }
+            }
+            Tile::Tree => {
+                if lumber >= rules.lumber_to_build {
+                    Tile::Lumber
+                } else {
+                    Tile::Tree
+                }
+            }
+            Tile::Lumber => {
+                if lumber >= rules.lumber_to_keep && tree >= rules.trees_to_keep {
+                    Tile::Lumber
+                } else {
+                    Tile::Open
+                }
+            }
+        }
+    }
+
+    pub fn step(&mut self) {
+        for i in 0..self.tiles.len() {
+            self.scratch[i] = self.next_tile(i);
+        }
+        self.finish_step();
+    }
+
+    // Same as `step`, with rows computed in parallel. Each row only writes its own part of
+    // `scratch`.
+    pub fn step_parallel(&mut self) {
+        let mut scratch = mem::take(&mut self.scratch);
+        scratch
+            .par_chunks_mut(self.width)
+            .enumerate()
+            .for_each(|(row, tiles)| {
+                for (x, tile) in tiles.iter_mut().enumerate() {
+                    *tile = self.next_tile(row * self.width + x);
+                }
+            });
+        self.scratch = scratch;
+        self.finish_step();
+    }
+
+    fn finish_step(&mut self) {
+        mem::swap(&mut self.tiles, &mut self.scratch);
+        self.unchanging = self.tiles == self.scratch;
+        self.time += 1;
+        self.values.push(self.resource_value());
+
+        if self.periodicity.is_none() {
+            let mut hasher = DefaultHasher::new();
+            self.tiles.hash(&mut hasher);
+            let hash = hasher.finish();
+            if let Some(prev) = self.history.insert(hash, self.time) {
+                self.periodicity = Some(self.time - prev);
+            }
+        }
+    }
+
+    fn trees(&self) -> usize {
+        self.tiles.iter().filter(|&&t| t == Tile::Tree).count()
+    }
+
+    fn lumbers(&self) -> usize {
+        self.tiles.iter().filter(|&&t| t == Tile::Lumber).count()
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.unchanging
+    }
+
+    pub fn resource_value(&self) -> usize {
+        self.trees() * self.lumbers()
+    }
+
+    // Steps until `minute` or until the area starts repeating, whichever is sooner. Once it
+    // repeats, the value at any later minute is the one at the same point of the cycle.
+    pub fn resource_value_at(&mut self, minute: u64) -> usize {
+        if self.is_static() && minute >= self.time as u64 {
+            return *self.values.last().unwrap();
+        }
+        while (self.time as u64) < minute && self.periodicity.is_none() {
+            self.step();
+        }
+        if minute <= self.time as u64 {
+            return self.values[minute as usize];
+        }
+        let period = self.periodicity.unwrap();
+        let start = self.time - period;
+        let offset = (minute - start as u64) % period as u64;
+        self.values[start + offset as usize]
+    }
+}
+
+impl fmt::Display for Area {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, t) in self.tiles.iter().enumerate() {
+            let c = match t {
+                Tile::Open => '.',
+                Tile::Tree => '|',
+                Tile::Lumber => '#',
+            };
+            write!(f, "{}", c)?;
+            if i % self.width == self.width - 1 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+const EXAMPLE: &str = ".#.#...|#.
+.....#|##|
+.|..|...#.
+..|#.....#
+#.#|||#|#|
+...#.||...
+.|....|...
+||...#|.#|
+|.||||..|.
+...#.|..|.
+";
+
+#[test]
+fn test_resource_value_at() -> Result<()> {
+    let mut area = Area::from_bytes(&mut EXAMPLE.bytes().map(Ok))?;
+    assert_eq!(area.resource_value_at(10), 1147);
+    assert_eq!(area.resource_value_at(3), area.values[3]);
+
+    // A patch that starts repeating every 28 minutes after a while. A far-off minute should match
+    // stepping to the same point of a later cycle.
+    let patch = ".#|....|
+#..####.
+.##.....
+....##||
+..#|.||.
+||||#...
+...||.|.
+...|#.#|
+";
+    let mut area = Area::from_bytes(&mut patch.bytes().map(Ok))?;
+    let value = area.resource_value_at(1_000_000_000);
+    assert_eq!(area.periodicity, Some(28));
+    let mut stepped = Area::from_bytes(&mut patch.bytes().map(Ok))?;
+    for _ in 0..200 + (1_000_000_000 - 200) % 28 {
+        stepped.step();
+    }
+    assert_eq!(value, stepped.resource_value());
+    assert_ne!(value, 0);
+    Ok(())
+}
+
+#[test]
+fn test_step_parallel() -> Result<()> {
+    let mut serial = Area::from_bytes(&mut EXAMPLE.bytes().map(Ok))?;
+    let mut parallel = Area::from_bytes(&mut EXAMPLE.bytes().map(Ok))?;
+    for _ in 0..10 {
+        serial.step();
+        parallel.step_parallel();
+        assert_eq!(parallel.to_string(), serial.to_string());
+    }
+    assert_eq!(parallel.resource_value(), 1147);
+    Ok(())
+}
+
+#[test]
+fn test_rules() -> Result<()> {
+    let mut area = Area::from_bytes(&mut EXAMPLE.bytes().map(Ok))?;
+    area.set_rules(Rules::default());
+    assert_eq!(area.resource_value_at(10), 1147);
+
+    let mut area = Area::from_bytes(&mut EXAMPLE.bytes().map(Ok))?;
+    area.set_rules(Rules {
+        trees_to_grow: 2,
+        ..Rules::default()
+    });
+    let value = area.resource_value_at(10);
+    assert_ne!(value, 1147);
+
+    // Nothing can change if it takes more neighbours than an acre has.
+    let mut area = Area::from_bytes(&mut EXAMPLE.bytes().map(Ok))?;
+    let initial = area.resource_value();
+    area.set_rules(Rules {
+        trees_to_grow: 9,
+        lumber_to_build: 9,
+        lumber_to_keep: 0,
+        trees_to_keep: 0,
+    });
+    area.step();
+    assert!(area.is_static());
+    assert_eq!(area.resource_value_at(1_000_000_000), initial);
+    Ok(())
+}
+
+#[test]
+fn test_is_static() -> Result<()> {
+    let mut area = Area::from_bytes(&mut "...\n...\n...\n".bytes().map(Ok))?;
+    assert!(!area.is_static());
+    area.step();
+    assert!(area.is_static());
+    assert_eq!(area.resource_value_at(1_000_000_000), 0);
+    assert_eq!(area.time, 1);
+
+    // Each lumber yard touches the other and both trees, which only ever see two lumber yards.
+    let mut area = Area::from_bytes(&mut "#|\n|#\n".bytes().map(Ok))?;
+    area.step();
+    assert!(area.is_static());
+    assert_eq!(area.resource_value_at(1_000_000_000), 4);
+
+    let mut area = Area::from_bytes(&mut EXAMPLE.bytes().map(Ok))?;
+    area.step();
+    assert!(!area.is_static());
+    Ok(())
+}
@@ -1,245 +1,487 @@
-use failure::{bail, ensure, Error};
+use failure::{bail, format_err, Error};
+use std::collections::HashMap;
+use std::env;
 use std::fmt;
-use std::io::{self, BufRead};
+use std::io;
 use std::result;
-use std::str::FromStr;
+use vm::{operands, other, Instruction, OpCode};
 
 type Result<T> = result::Result<T, Error>;
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum OpCode {
-    addr,
-    addi,
-    mulr,
-    muli,
-    banr,
-    bani,
-    borr,
-    bori,
-    setr,
-    seti,
-    gtir,
-    gtri,
-    gtrr,
-    eqir,
-    eqri,
-    eqrr,
-}
-
-impl FromStr for OpCode {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        match s {
-            "addr" => Ok(OpCode::addr),
-            "addi" => Ok(OpCode::addi),
-            "mulr" => Ok(OpCode::mulr),
-            "muli" => Ok(OpCode::muli),
-            "banr" => Ok(OpCode::banr),
-            "bani" => Ok(OpCode::bani),
-            "borr" => Ok(OpCode::borr),
-            "bori" => Ok(OpCode::bori),
-            "setr" => Ok(OpCode::setr),
-            "seti" => Ok(OpCode::seti),
-            "gtir" => Ok(OpCode::gtir),
-            "gtri" => Ok(OpCode::gtri),
-            "gtrr" => Ok(OpCode::gtrr),
-            "eqir" => Ok(OpCode::eqir),
-            "eqri" => Ok(OpCode::eqri),
-            "eqrr" => Ok(OpCode::eqrr),
-            _ => bail!("unknown opcode"),
+// Sum of the divisors of `n` that are at least `from`.
+fn divisor_sum(n: u64, from: u64) -> u64 {
+    let mut sum = 0;
+    let mut d = 1;
+    while d * d <= n {
+        if n.is_multiple_of(d) {
+            if d >= from {
+                sum += d;
+            }
+            if n / d != d && n / d >= from {
+                sum += n / d;
+            }
         }
+        d += 1;
     }
+    sum
 }
 
-impl fmt::Display for OpCode {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s = match self {
-            OpCode::addr => "addr",
-            OpCode::addi => "addi",
-            OpCode::mulr => "mulr",
-            OpCode::muli => "muli",
-            OpCode::banr => "banr",
-            OpCode::bani => "bani",
-            OpCode::borr => "borr",
-            OpCode::bori => "bori",
-            OpCode::setr => "setr",
-            OpCode::seti => "seti",
-            OpCode::gtir => "gtir",
-            OpCode::gtri => "gtri",
-            OpCode::gtrr => "gtrr",
-            OpCode::eqir => "eqir",
-            OpCode::eqri => "eqri",
-            OpCode::eqrr => "eqrr",
-        };
-        write!(f, "{}", s)
-    }
-}
-
-struct Instruction {
-    opcode: OpCode,
-    in1: u64,
-    in2: u64,
-    out: u64,
+// Every input slowly adds up the divisors of a number `n` with two nested loops, differing only
+// in register choice and operand order:
+//
+//          seti 1 _ b          outer: b = 1
+//   inner: mulr a b t          inner: if a * b == n { sum += a }
+//          eqrr t n t
+//          addr t ip ip
+//          addi ip 1 ip
+//          addr a sum sum
+//          addi b 1 b                 b += 1
+//          gtrr b n t                 if b <= n { goto inner }
+//          addr ip t ip
+//          seti inner-1 _ ip
+//          addi a 1 a                 a += 1
+//          gtrr a n t                 if a <= n { goto outer }
+//          addr t ip ip
+//          seti inner-2 _ ip
+//
+// Both loops run until their counter passes `n`, so they can be replaced by arithmetic.
+#[derive(Debug, PartialEq)]
+struct DivisorLoop {
+    inner: usize,
+    // Whether the outer loop around `inner` was recognized too.
+    outer: bool,
+    a: usize,
+    b: usize,
+    t: usize,
+    n: usize,
+    sum: usize,
 }
 
-impl fmt::Display for Instruction {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} {} {}", self.opcode, self.in1, self.in2, self.out)
+impl DivisorLoop {
+    fn find(bindip: usize, instructions: &[Instruction<u64>]) -> Vec<DivisorLoop> {
+        (1..instructions.len())
+            .filter_map(|inner| DivisorLoop::at(bindip, instructions, inner))
+            .collect()
     }
-}
 
-impl FromStr for Instruction {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        let mut words = s.split(' ');
-        let opcode = words.next().expect("opcode").parse()?;
-        let in1 = words.next().expect("in1").parse()?;
-        let in2 = words.next().expect("in2").parse()?;
-        let out = words.next().expect("out").parse()?;
-        ensure!(words.next().is_none(), "spurious input");
-        Ok(Instruction {
-            opcode,
-            in1,
-            in2,
-            out,
+    fn at(ip: usize, instructions: &[Instruction<u64>], inner: usize) -> Option<DivisorLoop> {
+        use OpCode::*;
+        let code = instructions.get(inner..inner + 9)?;
+        let (x, y, t) = operands(&code[0], mulr)?;
+        let (b, one, b_out) = operands(&code[5], addi)?;
+        (one == 1 && b_out == b).then_some(())?;
+        let a = other(x, y, b)?;
+        let (in1, in2, t_out) = operands(&code[1], eqrr)?;
+        let n = other(in1, in2, t)?;
+        let (in1, in2, sum) = operands(&code[4], addr)?;
+        (t_out == t && other(in1, in2, a)? == sum).then_some(())?;
+        let (in1, in2, ip_out) = operands(&code[2], addr)?;
+        (other(in1, in2, t)? == ip && ip_out == ip).then_some(())?;
+        (operands(&code[3], addi)? == (ip, 1, ip)).then_some(())?;
+        (operands(&code[6], gtrr)? == (b, n, t)).then_some(())?;
+        let (in1, in2, ip_out) = operands(&code[7], addr)?;
+        (other(in1, in2, t)? == ip && ip_out == ip).then_some(())?;
+        let (target, _, ip_out) = operands(&code[8], seti)?;
+        (target + 1 == inner && ip_out == ip).then_some(())?;
+
+        let registers = [a, b, t, n, sum, ip];
+        for (i, &r) in registers.iter().enumerate() {
+            (r < 6 && !registers[i + 1..].contains(&r)).then_some(())?;
+        }
+
+        let outer = (|| {
+            (operands(&instructions[inner - 1], seti)?.0 == 1).then_some(())?;
+            let code = instructions.get(inner - 1..inner + 13)?;
+            (code[0].out as usize == b).then_some(())?;
+            (operands(&code[10], addi)? == (a, 1, a)).then_some(())?;
+            (operands(&code[11], gtrr)? == (a, n, t)).then_some(())?;
+            let (in1, in2, ip_out) = operands(&code[12], addr)?;
+            (other(in1, in2, t)? == ip && ip_out == ip).then_some(())?;
+            let (target, _, ip_out) = operands(&code[13], seti)?;
+            (target + 2 == inner && ip_out == ip).then_some(())
+        })()
+        .is_some();
+
+        Some(DivisorLoop {
+            inner,
+            outer,
+            a,
+            b,
+            t,
+            n,
+            sum,
         })
     }
-}
 
-struct Machine {
-    registers: [u64; 6],
-    bindip: usize,
-    instructions: Vec<Instruction>,
-}
+    fn starts_at(&self, ip: usize) -> bool {
+        ip == self.inner || (self.outer && ip + 1 == self.inner)
+    }
 
-fn bool_to_u64(b: bool) -> u64 {
-    if b {
-        1
-    } else {
-        0
+    // Leaves the registers as they'd be once the loop starting at the instruction pointer exits.
+    fn run(&self, registers: &mut [u64; 6], bindip: usize) {
+        let (a, b, n) = (registers[self.a], registers[self.b], registers[self.n]);
+        if registers[bindip] as usize == self.inner {
+            // The body runs at least once, for every b up to n.
+            if a != 0 && n.is_multiple_of(a) && n / a >= b {
+                registers[self.sum] += a;
+            }
+            registers[self.b] = b.max(n) + 1;
+            registers[bindip] = (self.inner + 8) as u64;
+        } else {
+            registers[self.sum] += if n == 0 { 0 } else { divisor_sum(n, a.max(1)) };
+            registers[self.a] = a.max(n) + 1;
+            registers[self.b] = n.max(1) + 1;
+            registers[bindip] = (self.inner + 12) as u64;
+        }
+        registers[self.t] = 1;
     }
 }
 
+// The shared machine, with the divisor loops found in its program run as arithmetic.
+struct Machine {
+    machine: vm::Machine<u64>,
+    loops: Vec<DivisorLoop>,
+    steps: u64,
+    // When checking for infinite loops, the step at which each state was first seen. The
+    // registers include the instruction pointer, so a repeated state repeats forever.
+    seen: Option<HashMap<[u64; 6], u64>>,
+}
+
 impl Machine {
-    fn new(bindip: usize, instructions: Vec<Instruction>) -> Machine {
+    fn new(machine: vm::Machine<u64>) -> Machine {
         Machine {
-            registers: [0; 6],
-            bindip,
-            instructions,
+            loops: DivisorLoop::find(machine.bindip, &machine.instructions),
+            machine,
+            steps: 0,
+            seen: None,
         }
     }
 
-    fn reset(&mut self) {
-        self.registers = [0; 6];
+    fn detect_cycles(&mut self) {
+        self.seen = Some(HashMap::new());
+    }
+
+    fn start(&mut self, initial: [u64; 6]) {
+        self.machine.registers = initial;
+        self.steps = 0;
+        if let Some(seen) = &mut self.seen {
+            seen.clear();
+        }
     }
 
     fn reg(&self, r: u64) -> u64 {
-        self.registers[r as usize]
+        self.machine.registers[r as usize]
     }
 
     fn ip(&self) -> usize {
-        self.registers[self.bindip] as usize
+        self.machine.ip()
+    }
+
+    // Returns None once the instruction pointer leaves the program, without executing anything if
+    // it starts outside, as a `--rN` flag can set it.
+    fn step(&mut self) -> Result<Option<()>> {
+        let ip = self.ip();
+        if ip >= self.machine.instructions.len() {
+            return Ok(None);
+        }
+        let registers = self.machine.registers;
+        if let Some(seen) = &mut self.seen {
+            if let Some(first) = seen.insert(registers, self.steps) {
+                bail!(
+                    "infinite loop: step {} repeats step {}, ip={} {:?}",
+                    self.steps,
+                    first,
+                    ip,
+                    registers
+                );
+            }
+        }
+        self.steps += 1;
+        let m = &mut self.machine;
+        Ok(match self.loops.iter().find(|l| l.starts_at(ip)) {
+            Some(divisor_loop) => {
+                divisor_loop.run(&mut m.registers, m.bindip);
+                m.advance()
+            }
+            None => m.step(),
+        })
+    }
+
+    fn run(&mut self) -> Result<()> {
+        while let Some(()) = self.step()? {}
+        Ok(())
+    }
+
+    fn run_with(&mut self, initial: [u64; 6]) -> Result<u64> {
+        self.start(initial);
+        self.run()?;
+        Ok(self.reg(0))
     }
 
-    fn execute(&mut self) {
+    // Renders the program as pseudocode. Reads of the instruction pointer are replaced by the
+    // instruction's address and writes to it become jumps to the instruction after the value.
+    fn disassemble(&self) -> String {
+        let width = self
+            .machine
+            .instructions
+            .len()
+            .saturating_sub(1)
+            .to_string()
+            .len();
+        self.machine
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(ip, instruction)| {
+                format!(
+                    "{:>width$}: {}\n",
+                    ip,
+                    self.pseudocode(ip, instruction),
+                    width = width
+                )
+            })
+            .collect()
+    }
+
+    fn pseudocode(&self, ip: usize, instruction: &Instruction<u64>) -> String {
         use OpCode::*;
-        let Instruction {
-            opcode,
-            in1: a,
-            in2: b,
-            out,
-        } = self.instructions[self.ip()];
-        self.registers[out as usize] = match opcode {
-            addr => self.reg(a) + self.reg(b),
-            addi => self.reg(a) + b,
-            mulr => self.reg(a) * self.reg(b),
-            muli => self.reg(a) * b,
-            banr => self.reg(a) & self.reg(b),
-            bani => self.reg(a) & b,
-            borr => self.reg(a) | self.reg(b),
-            bori => self.reg(a) | b,
-            setr => self.reg(a),
-            seti => a,
-            gtir => bool_to_u64(a > self.reg(b)),
-            gtri => bool_to_u64(self.reg(a) > b),
-            gtrr => bool_to_u64(self.reg(a) > self.reg(b)),
-            eqir => bool_to_u64(a == self.reg(b)),
-            eqri => bool_to_u64(self.reg(a) == b),
-            eqrr => bool_to_u64(self.reg(a) == self.reg(b)),
+        let (register1, register2) = instruction.opcode.register_inputs();
+        let operand = |is_register: bool, x: u64| match is_register {
+            true if x as usize == self.machine.bindip => Operand::Value(ip as u64),
+            true => Operand::Register(x),
+            false => Operand::Value(x),
+        };
+        let a = operand(register1, instruction.in1);
+        let b = operand(register2, instruction.in2);
+        let (symbol, apply): (&str, fn(u64, u64) -> u64) = match instruction.opcode {
+            addr | addi => ("+", u64::wrapping_add),
+            mulr | muli => ("*", u64::wrapping_mul),
+            banr | bani => ("&", |x, y| x & y),
+            borr | bori => ("|", |x, y| x | y),
+            gtir | gtri | gtrr => (">", |x, y| (x > y) as u64),
+            eqir | eqri | eqrr => ("==", |x, y| (x == y) as u64),
+            setr | seti => return self.assign(instruction.out, a, None),
         };
+        match (a, b) {
+            (Operand::Value(x), Operand::Value(y)) => {
+                self.assign(instruction.out, Operand::Value(apply(x, y)), None)
+            }
+            _ => self.assign(instruction.out, a, Some((symbol, b))),
+        }
     }
 
-    // The input assembly slowly adds all divisors.
-    fn shortcut(&mut self) {
-        match self.registers {
-            [_, _, _, 3, _, _] if self.reg(5) * self.reg(4) < self.reg(1) => {
-                self.registers[4] = self.reg(1) / self.reg(5);
-                println!("set reg 4 to {}", self.reg(4));
-            }
-            [_, _, _, 9, _, _] if self.reg(4) <= self.reg(1) && self.reg(5) > 1 => {
-                self.registers[4] = self.reg(1) + 1;
-                println!("set reg 4 to {}", self.reg(4));
+    // Renders `out = a` or `out = a symbol b`.
+    fn assign(&self, out: u64, a: Operand, rest: Option<(&str, Operand)>) -> String {
+        if out as usize != self.machine.bindip {
+            return match rest {
+                Some((symbol, b)) => format!("r{} = {} {} {}", out, a, symbol, b),
+                None => format!("r{} = {}", out, a),
+            };
+        }
+        match (a, rest) {
+            (Operand::Value(x), None) if x + 1 >= self.machine.instructions.len() as u64 => {
+                "halt".to_string()
             }
-            [_, _, _, 13, _, _]
-                if self.reg(5) > 2
-                    && self.reg(5) < self.reg(1)
-                    && (self.reg(1) - self.reg(5)) % 2 == 0
-                    && self.reg(4) == self.reg(1) + 1 =>
-            {
-                let sum: u64 = (self.reg(5)..self.reg(1))
-                    .filter(|x| self.reg(1) % x == 0)
-                    .sum();
-                self.registers[0] = self.reg(0) + sum;
-                self.registers[5] = self.reg(1);
-                println!("set reg 0 to {}, 5 to {}", self.reg(0), self.reg(5));
+            (Operand::Value(x), None) => format!("goto {}", x + 1),
+            (Operand::Register(r), Some(("+", Operand::Value(x))))
+            | (Operand::Value(x), Some(("+", Operand::Register(r)))) => {
+                format!("goto r{} + {}", r, x + 1)
             }
-            _ => {}
+            (a, Some((symbol, b))) => format!("goto ({} {} {}) + 1", a, symbol, b),
+            (a, None) => format!("goto {} + 1", a),
         }
     }
+}
 
-    fn step(&mut self) -> Option<()> {
-        self.shortcut();
-        self.execute();
-        self.registers[self.bindip] += 1;
-        if self.ip() < self.instructions.len() {
-            Some(())
-        } else {
-            None
+#[derive(Clone, Copy)]
+enum Operand {
+    Register(u64),
+    Value(u64),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "r{}", r),
+            Operand::Value(x) => write!(f, "{}", x),
         }
     }
+}
+
+#[test]
+fn test_disassemble() -> Result<()> {
+    let source = "addi 3 7 3\nseti 1 9 5\nmulr 5 4 2\neqrr 2 1 2\naddr 2 3 3\ngtri 4 7 0\n\
+                  setr 3 0 1\nmulr 3 3 3\nbani 1 255 1\nborr 0 3 3\nsetr 1 0 3\nseti 0 0 3";
+    let instructions = source.lines().map(str::parse).collect::<vm::Result<_>>()?;
+    let m = Machine::new(vm::Machine::new(3, instructions));
+    let expected = [
+        " 0: goto 8",
+        " 1: r5 = 1",
+        " 2: r2 = r5 * r4",
+        " 3: r2 = r2 == r1",
+        " 4: goto r2 + 5",
+        " 5: r0 = r4 > 7",
+        " 6: r1 = 6",
+        " 7: halt",
+        " 8: r1 = r1 & 255",
+        " 9: goto (r0 | 9) + 1",
+        "10: goto r1 + 1",
+        "11: goto 1",
+    ];
+    assert_eq!(m.disassemble().lines().collect::<Vec<_>>(), expected);
+    Ok(())
+}
+
+#[test]
+fn test_divisor_loop() -> Result<()> {
+    // The same loops as the inputs, with other registers and operands swapped around.
+    let program = |n: u64| -> Result<Vec<Instruction<u64>>> {
+        let source = format!(
+            "seti {} 0 4\nseti 1 0 1\nseti 1 0 5\nmulr 5 1 3\neqrr 4 3 3\naddr 2 3 2\n\
+             addi 2 1 2\naddr 0 1 0\naddi 5 1 5\ngtrr 5 4 3\naddr 3 2 2\nseti 2 0 2\n\
+             addi 1 1 1\ngtrr 1 4 3\naddr 2 3 2\nseti 1 0 2\nmulr 2 2 2",
+            n
+        );
+        Ok(source.lines().map(str::parse).collect::<vm::Result<_>>()?)
+    };
+
+    let mut m = Machine::new(vm::Machine::new(2, program(12)?));
+    let expected = DivisorLoop {
+        inner: 3,
+        outer: true,
+        a: 1,
+        b: 5,
+        t: 3,
+        n: 4,
+        sum: 0,
+    };
+    assert_eq!(m.loops, vec![expected]);
+    m.run()?;
+    assert_eq!(m.reg(0), 28);
 
-    fn run(&mut self) {
-        while let Some(()) = self.step() {}
+    for n in &[1, 12, 25, 30] {
+        let mut fast = Machine::new(vm::Machine::new(2, program(*n)?));
+        let mut slow = Machine::new(vm::Machine::new(2, program(*n)?));
+        slow.loops.clear();
+        fast.run()?;
+        slow.run()?;
+        assert_eq!(fast.machine.registers, slow.machine.registers);
     }
+
+    // Only the inner loop is collapsed when the outer one looks different.
+    let mut instructions = program(25)?;
+    instructions[12] = "addi 1 2 1".parse()?;
+    let mut m = Machine::new(vm::Machine::new(2, instructions));
+    assert!(m.loops[0].starts_at(3) && !m.loops[0].starts_at(2));
+    m.run()?;
+    assert_eq!(m.reg(0), 1 + 5 + 25);
+
+    // Anything else runs as written.
+    let mut instructions = program(12)?;
+    instructions[8] = "addi 5 2 5".parse()?;
+    assert!(Machine::new(vm::Machine::new(2, instructions))
+        .loops
+        .is_empty());
+
+    let mut m = Machine::new(vm::Machine::new(2, program(1_000_000)?));
+    m.run()?;
+    assert_eq!(m.reg(0), 2_480_437);
+    Ok(())
+}
+
+#[test]
+fn test_run_with() -> Result<()> {
+    let instructions = vec![
+        "addr 0 1 0".parse()?,
+        "muli 0 2 0".parse()?,
+        "addi 2 7 2".parse()?,
+    ];
+    let mut m = Machine::new(vm::Machine::new(5, instructions));
+    assert_eq!(m.run_with([3, 4, 0, 0, 0, 0])?, 14);
+    assert_eq!(m.machine.registers, [14, 4, 7, 0, 0, 3]);
+    assert_eq!(m.run_with([0, 0, 1, 0, 0, 0])?, 0);
+    assert_eq!(m.machine.registers, [0, 0, 8, 0, 0, 3]);
+    // Starting past the end halts straight away, as does starting on it.
+    assert_eq!(m.run_with([5, 0, 0, 0, 0, 100])?, 5);
+    assert_eq!(m.machine.registers, [5, 0, 0, 0, 0, 100]);
+    assert_eq!(m.run_with([5, 0, 0, 0, 0, 3])?, 5);
+    Ok(())
+}
+
+#[test]
+fn test_detect_cycles() -> Result<()> {
+    let instructions = vec!["seti 7 0 1".parse()?, "seti 0 0 0".parse()?];
+    let mut m = Machine::new(vm::Machine::new(0, instructions));
+    m.detect_cycles();
+    let err = m.run_with([0; 6]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "infinite loop: step 2 repeats step 1, ip=1 [1, 7, 0, 0, 0, 0]"
+    );
+
+    let instructions = vec!["addr 0 1 0".parse()?, "muli 0 2 0".parse()?];
+    let mut m = Machine::new(vm::Machine::new(5, instructions));
+    m.detect_cycles();
+    assert_eq!(m.run_with([3, 4, 0, 0, 0, 0])?, 14);
+    Ok(())
 }
 
 fn main() -> Result<()> {
+    let mut trace = false;
+    let mut detect_cycles = false;
+    let mut disassemble = false;
+    let mut initial: Option<[u64; 6]> = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => trace = true,
+            "--detect-cycles" => detect_cycles = true,
+            "--disassemble" => disassemble = true,
+            flag if flag.starts_with("--r") => {
+                let register = flag[3..]
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&r| r < 6)
+                    .ok_or_else(|| format_err!("unexpected argument {}", arg))?;
+                let value = args
+                    .next()
+                    .ok_or_else(|| format_err!("missing {} value", arg))?;
+                initial.get_or_insert([0; 6])[register] = value.parse()?;
+            }
+            _ => bail!("unexpected argument {}", arg),
+        }
+    }
+
     let stdin = io::stdin();
-    let mut lines = stdin.lock().lines();
-    let bindip = lines.next().expect("empty input")?;
-    ensure!(bindip.starts_with("#ip "), "#ip");
-    let bindip = bindip[4..].parse()?;
-    let instructions: Vec<Instruction> = lines
-        .map(|l| l.unwrap().parse())
-        .collect::<Result<Vec<Instruction>>>()?;
-
-    let mut m = Machine::new(bindip, instructions);
-    m.run();
-    println!("{}", m.registers[0]);
-
-    m.reset();
-    m.registers[0] = 1;
-    println!("#ip {}", bindip);
-    let mut exit = false;
-    while !exit {
-        let prefix = format!("ip={} {:?} {}", m.ip(), m.registers, m.instructions[m.ip()]);
-        exit = m.step().is_none();
-        println!("{} {:?}", prefix, m.registers);
-    }
-    println!("{}", m.registers[0]);
+    let machine = vm::Machine::parse(stdin.lock())?;
+
+    // Without `--rN` flags, run both parts: r0 starting at 0, then at 1.
+    let starts = match initial {
+        Some(initial) => vec![initial],
+        None => vec![[0; 6], [1, 0, 0, 0, 0, 0]],
+    };
+    let mut m = Machine::new(machine);
+    if disassemble {
+        print!("{}", m.disassemble());
+        return Ok(());
+    }
+    if detect_cycles {
+        m.detect_cycles();
+    }
+    for (i, &start) in starts.iter().enumerate() {
+        if trace && i + 1 == starts.len() {
+            m.start(start);
+            println!("#ip {}", m.machine.bindip);
+            while let Some(instruction) = m.machine.instructions.get(m.ip()) {
+                let prefix = format!("ip={} {:?} {}", m.ip(), m.machine.registers, instruction);
+                m.step()?;
+                println!("{} {:?}", prefix, m.machine.registers);
+            }
+            println!("{}", m.reg(0));
+        } else {
+            println!("{}", m.run_with(start)?);
+        }
+    }
 
     Ok(())
 }
@@ -1,10 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::error::Error;
 use std::io::{self, Read};
-use std::mem;
 use std::result;
-use std::error::Error;
 
-type Result<T> = result::Result<T, Box<Error>>;
+type Result<T> = result::Result<T, Box<dyn Error>>;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Coord(i32, i32);
@@ -22,95 +22,287 @@ impl Door {
     }
 }
 
+#[derive(Debug, PartialEq)]
+struct RoomSummary {
+    furthest: u32,
+    at_least_1000: usize,
+}
+
 struct Map {
     doors: HashSet<Door>,
 }
 
 impl Map {
-    fn bfs(s: &[u8], mut i: usize, pos: &mut Vec<Coord>, doors: &mut HashSet<Door>) -> usize {
-        let start_pos = pos.clone();
-        let mut end_pos = Vec::new();
-        while i < s.len() {
-            let (offset_x, offset_y) = match s[i] {
+    // Follows the regex with every room it could have reached so far. Each open group keeps the
+    // rooms it started from and the rooms its finished branches reached; the whole regex acts as
+    // the outermost group.
+    fn from_bytes(s: &[u8]) -> Result<Map> {
+        if s.first() != Some(&b'^') {
+            return Err("expected regex to start with `^`".into());
+        }
+        let mut doors = HashSet::new();
+        let mut pos = vec![Coord(0, 0)];
+        let mut groups = vec![(pos.clone(), Vec::new())];
+        for (i, &c) in s.iter().enumerate().skip(1) {
+            let (offset_x, offset_y) = match c {
                 b'N' => (0, 1),
                 b'E' => (1, 0),
                 b'S' => (0, -1),
                 b'W' => (-1, 0),
                 b'(' => {
-                    i = Map::bfs(s, i + 1, pos, doors);
+                    groups.push((pos.clone(), Vec::new()));
                     continue;
                 }
                 b'|' => {
+                    let (start_pos, end_pos) = groups.last_mut().unwrap();
                     end_pos.extend_from_slice(&pos);
-                    pos.clear();
-                    pos.extend_from_slice(&start_pos);
-                    i += 1;
+                    pos.clone_from(start_pos);
                     continue;
                 }
-                b')' => {
-                    mem::swap(pos, &mut end_pos);
-                    pos.extend_from_slice(&end_pos);
-                    pos.sort();
-                    pos.dedup();
-                    return i + 1;
+                b')' if groups.len() > 1 => {
+                    let (_, mut end_pos) = groups.pop().unwrap();
+                    end_pos.extend_from_slice(&pos);
+                    end_pos.sort();
+                    end_pos.dedup();
+                    pos = end_pos;
+                    continue;
                 }
-                b'$' => {
-                    assert_eq!(&s[i..], b"$\n");
-                    break;
+                b'$' if groups.len() == 1 && s[i + 1..].iter().all(u8::is_ascii_whitespace) => {
+                    return Ok(Map { doors });
                 }
-                _ => panic!("Unknown char"),
+                _ => return Err(format!("unexpected `{}` at {}", c as char, i).into()),
             };
             for pos in pos.iter_mut() {
                 let Coord(x, y) = *pos;
-                mem::replace(pos, Coord(x + offset_x, y + offset_y));
+                *pos = Coord(x + offset_x, y + offset_y);
                 doors.insert(Door::new(Coord(x, y), *pos));
             }
-            i += 1;
         }
-        s.len()
+        Err("expected regex to end with `$`".into())
     }
 
-    fn from_bytes(s: &[u8]) -> Result<Map> {
-        assert_eq!(s[0], b'^');
-        let mut doors = HashSet::new();
-        let i = Map::bfs(s, 1, &mut vec![Coord(0, 0)], &mut doors);
-        assert_eq!(i, s.len());
-        Ok(Map { doors })
+    // Draws the map like the puzzle: rooms as `.` with the start as `X`, doors as `|` or `-`, and
+    // walls as `#` everywhere else.
+    fn render(&self) -> String {
+        let rooms = self.doors.iter().flat_map(|&Door(a, b)| vec![a, b]);
+        let rooms: Vec<_> = rooms.chain(Some(Coord(0, 0))).collect();
+        let min_x = rooms.iter().map(|c| c.0).min().unwrap();
+        let max_x = rooms.iter().map(|c| c.0).max().unwrap();
+        let min_y = rooms.iter().map(|c| c.1).min().unwrap();
+        let max_y = rooms.iter().map(|c| c.1).max().unwrap();
+        let (width, height) = (2 * (max_x - min_x + 1) + 1, 2 * (max_y - min_y + 1) + 1);
+        let mut grid = vec![vec![b'#'; width as usize]; height as usize];
+        // North is up, so rows count down from the northernmost room.
+        let cell = |x: i32, y: i32| {
+            let (row, col) = (2 * (max_y - y) + 1, 2 * (x - min_x) + 1);
+            (row as usize, col as usize)
+        };
+        for &room in &rooms {
+            let (row, col) = cell(room.0, room.1);
+            grid[row][col] = if room == Coord(0, 0) { b'X' } else { b'.' };
+        }
+        for &Door(a, b) in &self.doors {
+            let ((row_a, col_a), (row_b, col_b)) = (cell(a.0, a.1), cell(b.0, b.1));
+            let door = if row_a == row_b { b'|' } else { b'-' };
+            grid[(row_a + row_b) / 2][(col_a + col_b) / 2] = door;
+        }
+        grid.into_iter()
+            .map(|row| String::from_utf8(row).unwrap() + "\n")
+            .collect()
     }
 
-    fn distances(&self) -> Vec<u32> {
-        let mut distances = Vec::new();
-        let mut visited = HashSet::new();
-        let mut stack = vec![(0, Coord(0, 0))];
-        while let Some((dist, Coord(x, y))) = stack.pop() {
-            if !visited.insert(Coord(x, y)) {
-                continue;
-            }
-            distances.push(dist);
+    // The fewest doors to each room from the origin. Breadth-first, so each room is reached first
+    // along one of its shortest paths.
+    fn distances(&self) -> HashMap<Coord, u32> {
+        let mut distances = HashMap::new();
+        distances.insert(Coord(0, 0), 0);
+        let mut queue = VecDeque::new();
+        queue.push_back((0, Coord(0, 0)));
+        while let Some((dist, Coord(x, y))) = queue.pop_front() {
             for (offset_x, offset_y) in &[(0, 1), (1, 0), (0, -1), (-1, 0)] {
                 let neighbor = Coord(x + offset_x, y + offset_y);
-                if Coord(x, y) < neighbor && self.doors.contains(&Door(Coord(x, y), neighbor)) || self.doors.contains(&Door(neighbor, Coord(x, y))) {
-                    stack.push((dist + 1, neighbor));
+                let door = Door::new(Coord(x, y), neighbor);
+                if self.doors.contains(&door) && !distances.contains_key(&neighbor) {
+                    distances.insert(neighbor, dist + 1);
+                    queue.push_back((dist + 1, neighbor));
                 }
             }
         }
         distances
     }
 
+    // The fewest doors to `target` from the origin, or None if no door leads there.
+    fn shortest_path(&self, target: Coord) -> Option<u32> {
+        self.distances().get(&target).copied()
+    }
+
     fn furthest_room(&self) -> u32 {
-        self.distances().into_iter().max().unwrap()
+        self.distances().values().copied().max().unwrap()
+    }
+
+    fn summary(&self) -> RoomSummary {
+        let distances = self.distances();
+        RoomSummary {
+            furthest: distances.values().copied().max().unwrap(),
+            at_least_1000: distances.values().filter(|&&d| d >= 1000).count(),
+        }
+    }
+}
+
+#[test]
+fn test_from_bytes() -> Result<()> {
+    // A corridor north with each step in a group of its own, nested far deeper than the call
+    // stack would allow if every group recursed.
+    let n = 100_000;
+    let regex = format!("^{}{}$\n", "(N".repeat(n), ")".repeat(n));
+    let map = Map::from_bytes(regex.as_bytes())?;
+    assert_eq!(map.doors.len(), n);
+    assert_eq!(map.furthest_room(), n as u32);
+
+    for &(regex, err) in &[
+        (&b"WNE$"[..], "expected regex to start with `^`"),
+        (b"^WNE", "expected regex to end with `$`"),
+        (b"^W(NE$", "unexpected `$` at 5"),
+        (b"^WN)E$", "unexpected `)` at 3"),
+        (b"^WxE$", "unexpected `x` at 2"),
+        (b"^WE$E", "unexpected `$` at 3"),
+    ] {
+        assert_eq!(Map::from_bytes(regex).err().unwrap().to_string(), err);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_summary() -> Result<()> {
+    let samples: &[(&[u8], u32)] = &[
+        (b"^WNE$", 3),
+        (b"^ENWWW(NEEE|SSE(EE|N))$", 10),
+        (b"^ENNWSWW(NEWS|)SSSEEN(WNSE|)EE(SWEN|)NNN$", 18),
+        (b"^ESSWWN(E|NNENN(EESS(WNSE|)SSS|WWWSSSSE(SW|NNNE)))$", 23),
+        (
+            b"^WSSEESWWWNW(S|NENNEEEENN(ESSSSW(NWSW|SSEN)|WSWWN(E|WWS(E|SS))))$",
+            31,
+        ),
+    ];
+    for &(regex, furthest) in samples {
+        let summary = Map::from_bytes(regex)?.summary();
+        assert_eq!(
+            summary,
+            RoomSummary {
+                furthest,
+                at_least_1000: 0
+            }
+        );
+    }
+
+    let corridor = format!("^{}$", "E".repeat(1005));
+    let summary = Map::from_bytes(corridor.as_bytes())?.summary();
+    assert_eq!(
+        summary,
+        RoomSummary {
+            furthest: 1005,
+            at_least_1000: 6
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_distances() -> Result<()> {
+    // Two routes around the same loop: every room is at most 4 doors away, but walking the loop
+    // the long way round would reach the last one at 7.
+    let map = Map::from_bytes(b"^(EEE|NEEES)$")?;
+    let mut distances: Vec<_> = map.distances().into_values().collect();
+    distances.sort_unstable();
+    assert_eq!(distances, vec![0, 1, 1, 2, 2, 3, 3, 4]);
+    assert_eq!(map.furthest_room(), 4);
+    Ok(())
+}
+
+#[test]
+fn test_render() -> Result<()> {
+    let expected = ["#####", "#.|.#", "#-###", "#.|X#", "#####", ""];
+    assert_eq!(Map::from_bytes(b"^WNE$")?.render(), expected.join("\n"));
+    let expected = [
+        "#########",
+        "#.|.|.|.#",
+        "#-#######",
+        "#.|.|.|.#",
+        "#-#####-#",
+        "#.#.#X|.#",
+        "#-#-#####",
+        "#.|.|.|.#",
+        "#########",
+        "",
+    ];
+    assert_eq!(
+        Map::from_bytes(b"^ENWWW(NEEE|SSE(EE|N))$")?.render(),
+        expected.join("\n")
+    );
+    assert_eq!(Map::from_bytes(b"^$")?.render(), "###\n#X#\n###\n");
+    Ok(())
+}
+
+#[test]
+fn test_shortest_path() -> Result<()> {
+    let map = Map::from_bytes(b"^ENWWW(NEEE|SSE(EE|N))$")?;
+    assert_eq!(map.shortest_path(Coord(0, 0)), Some(0));
+    assert_eq!(map.shortest_path(Coord(-2, 1)), Some(5));
+    assert_eq!(map.shortest_path(Coord(0, 2)), Some(8));
+    assert_eq!(map.shortest_path(Coord(1, -1)), Some(10));
+    assert_eq!(map.shortest_path(Coord(5, 5)), None);
+
+    let map = Map::from_bytes(b"^(EEE|NEEES)$")?;
+    assert_eq!(map.shortest_path(Coord(3, 1)), Some(4));
+    Ok(())
+}
+
+// Parses a room given as `X,Y`, with north and east positive.
+fn parse_room(s: &str) -> Result<Coord> {
+    let mut parts = s.split(',').map(|n| n.trim().parse());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(x)), Some(Ok(y)), None) => Ok(Coord(x, y)),
+        _ => Err(format!("expected room like `3,-2`, got `{}`", s).into()),
     }
 }
 
 fn main() -> Result<()> {
     assert_eq!(Map::from_bytes(b"^WNE$\n")?.furthest_room(), 3);
-    assert_eq!(Map::from_bytes(b"^ENWWW(NEEE|SSE(EE|N))$\n")?.furthest_room(), 10);
-    assert_eq!(Map::from_bytes(b"^ENNWSWW(NEWS|)SSSEEN(WNSE|)EE(SWEN|)NNN$\n")?.furthest_room(), 18);
+    assert_eq!(
+        Map::from_bytes(b"^ENWWW(NEEE|SSE(EE|N))$\n")?.furthest_room(),
+        10
+    );
+    assert_eq!(
+        Map::from_bytes(b"^ENNWSWW(NEWS|)SSSEEN(WNSE|)EE(SWEN|)NNN$\n")?.furthest_room(),
+        18
+    );
     assert_eq!(Map::from_bytes(b"^(N|S)(E|W)$\n")?.doors.len(), 6);
 
-    let bytes: Vec<u8> = io::stdin().bytes().collect::<result::Result<_, _>>()?;
+    let mut rooms = Vec::new();
+    let mut render = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--render" => render = true,
+            "--room" => rooms.push(parse_room(&args.next().ok_or("missing room")?)?),
+            _ => return Err(format!("unexpected argument {}", arg).into()),
+        }
+    }
+
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes)?;
     let map = Map::from_bytes(&bytes)?;
-    println!("{}", map.furthest_room());
-    println!("{}", map.distances().into_iter().filter(|&d| d >= 1000).count());
+    if render {
+        print!("{}", map.render());
+    }
+    let summary = map.summary();
+    println!("{}", summary.furthest);
+    println!("{}", summary.at_least_1000);
+    for room in rooms {
+        match map.shortest_path(room) {
+            Some(doors) => println!("{}", doors),
+            None => return Err(format!("no doors lead to room {},{}", room.0, room.1).into()),
+        }
+    }
     Ok(())
 }
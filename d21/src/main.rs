@@ -1,269 +1,310 @@
-use failure::{bail, ensure, Error};
+use failure::{bail, Error};
 use std::collections::HashSet;
-use std::fmt;
-use std::io::{self, BufRead};
+use std::env;
+use std::io;
 use std::result;
-use std::str::FromStr;
+use vm::{operands, other, Instruction, Machine, OpCode};
 
 type Result<T> = result::Result<T, Error>;
 
-#[allow(non_camel_case_types)]
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum OpCode {
-    addr,
-    addi,
-    mulr,
-    muli,
-    banr,
-    bani,
-    borr,
-    bori,
-    setr,
-    seti,
-    gtir,
-    gtri,
-    gtrr,
-    eqir,
-    eqri,
-    eqrr,
+// Every input divides a register `n` by 256 by counting up until the next multiple passes it:
+//
+//   loop:  addi t 1 u          loop: if (t + 1) * k > n { goto exit }
+//          muli u k u
+//          gtrr u n u
+//          addr u ip ip
+//          addi ip 1 ip
+//          seti exit-1 _ ip
+//          addi t 1 t                t += 1
+//          seti loop-1 _ ip            goto loop
+//
+// It takes tens of thousands of steps each time, but ends with t = n / k unless t starts past it.
+#[derive(Debug, PartialEq)]
+struct DivisionLoop {
+    start: usize,
+    exit: usize,
+    t: usize,
+    u: usize,
+    n: usize,
+    k: u64,
 }
 
-impl FromStr for OpCode {
-    type Err = Error;
+impl DivisionLoop {
+    fn find(bindip: usize, instructions: &[Instruction<u64>]) -> Vec<DivisionLoop> {
+        (0..instructions.len())
+            .filter_map(|start| DivisionLoop::at(bindip, instructions, start))
+            .collect()
+    }
+
+    fn at(ip: usize, instructions: &[Instruction<u64>], start: usize) -> Option<DivisionLoop> {
+        use OpCode::*;
+        let code = instructions.get(start..start + 8)?;
+        let (t, one, u) = operands(&code[0], addi)?;
+        (one == 1).then_some(())?;
+        let (in1, k, out) = operands(&code[1], muli)?;
+        (in1 == u && out == u && k > 0).then_some(())?;
+        let (in1, n, out) = operands(&code[2], gtrr)?;
+        (in1 == u && out == u).then_some(())?;
+        let (in1, in2, ip_out) = operands(&code[3], addr)?;
+        (other(in1, in2, u)? == ip && ip_out == ip).then_some(())?;
+        (operands(&code[4], addi)? == (ip, 1, ip)).then_some(())?;
+        let (target, _, ip_out) = operands(&code[5], seti)?;
+        (ip_out == ip).then_some(())?;
+        (operands(&code[6], addi)? == (t, 1, t)).then_some(())?;
+        let (target_back, _, ip_out) = operands(&code[7], seti)?;
+        (target_back + 1 == start && ip_out == ip).then_some(())?;
 
-    fn from_str(s: &str) -> Result<Self> {
-        match s {
-            "addr" => Ok(OpCode::addr),
-            "addi" => Ok(OpCode::addi),
-            "mulr" => Ok(OpCode::mulr),
-            "muli" => Ok(OpCode::muli),
-            "banr" => Ok(OpCode::banr),
-            "bani" => Ok(OpCode::bani),
-            "borr" => Ok(OpCode::borr),
-            "bori" => Ok(OpCode::bori),
-            "setr" => Ok(OpCode::setr),
-            "seti" => Ok(OpCode::seti),
-            "gtir" => Ok(OpCode::gtir),
-            "gtri" => Ok(OpCode::gtri),
-            "gtrr" => Ok(OpCode::gtrr),
-            "eqir" => Ok(OpCode::eqir),
-            "eqri" => Ok(OpCode::eqri),
-            "eqrr" => Ok(OpCode::eqrr),
-            _ => bail!("unknown opcode"),
+        let registers = [t, u, n, ip];
+        for (i, &r) in registers.iter().enumerate() {
+            (r < 6 && !registers[i + 1..].contains(&r)).then_some(())?;
         }
+        Some(DivisionLoop {
+            start,
+            exit: target + 1,
+            t,
+            u,
+            n,
+            k: k as u64,
+        })
+    }
+
+    // Leaves the registers as they'd be once the loop exits.
+    fn run(&self, registers: &mut [u64; 6], bindip: usize) {
+        registers[self.t] = registers[self.t].max(registers[self.n] / self.k);
+        registers[self.u] = 1;
+        registers[bindip] = (self.exit - 1) as u64;
     }
 }
 
-impl fmt::Display for OpCode {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s = match self {
-            OpCode::addr => "addr",
-            OpCode::addi => "addi",
-            OpCode::mulr => "mulr",
-            OpCode::muli => "muli",
-            OpCode::banr => "banr",
-            OpCode::bani => "bani",
-            OpCode::borr => "borr",
-            OpCode::bori => "bori",
-            OpCode::setr => "setr",
-            OpCode::seti => "seti",
-            OpCode::gtir => "gtir",
-            OpCode::gtri => "gtri",
-            OpCode::gtrr => "gtrr",
-            OpCode::eqir => "eqir",
-            OpCode::eqri => "eqri",
-            OpCode::eqrr => "eqrr",
-        };
-        write!(f, "{}", s)
+// Steps `m`, skipping to the end of any division loop it's at the start of.
+fn step(m: &mut Machine<u64>, loops: &[DivisionLoop]) -> Option<()> {
+    let ip = m.ip();
+    match loops.iter().find(|l| l.start == ip) {
+        Some(division_loop) => {
+            division_loop.run(&mut m.registers, m.bindip);
+            m.advance()
+        }
+        None => m.step(),
     }
 }
 
-struct Instruction {
-    opcode: OpCode,
-    in1: u64,
-    in2: u64,
-    out: u64,
+// What register 0 is compared with.
+#[derive(Debug, PartialEq)]
+enum Compared {
+    Register(usize),
+    Value(u64),
 }
 
-impl fmt::Display for Instruction {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {} {} {}", self.opcode, self.in1, self.in2, self.out)
+// The only equality test reading register 0, and what it compares register 0 with.
+fn comparison(instructions: &[Instruction<u64>]) -> Result<(usize, Compared)> {
+    use OpCode::*;
+    let mut found = Vec::new();
+    for (ip, i) in instructions.iter().enumerate() {
+        let compared = match (i.opcode, i.in1, i.in2) {
+            (eqrr, 0, 0) => Compared::Value(0),
+            (eqrr, 0, r) | (eqrr, r, 0) => Compared::Register(r as usize),
+            (eqri, 0, value) | (eqir, value, 0) => Compared::Value(value),
+            _ => continue,
+        };
+        found.push((ip, compared));
+    }
+    match found.len() {
+        0 => bail!("no comparison against register 0"),
+        1 => Ok(found.remove(0)),
+        _ => {
+            let ips: Vec<_> = found.iter().map(|(ip, _)| ip.to_string()).collect();
+            bail!(
+                "several comparisons against register 0, at {}",
+                ips.join(" ")
+            )
+        }
     }
 }
 
-impl FromStr for Instruction {
-    type Err = Error;
-
-    fn from_str(s: &str) -> Result<Self> {
-        let mut words = s.split(' ');
-        let opcode = words.next().expect("opcode").parse()?;
-        let in1 = words.next().expect("in1").parse()?;
-        let in2 = words.next().expect("in2").parse()?;
-        let out = words.next().expect("out").parse()?;
-        ensure!(words.next().is_none(), "spurious input");
-        Ok(Instruction {
-            opcode,
-            in1,
-            in2,
-            out,
-        })
+// The distinct values register 0 is compared with, in order, until they start repeating or the
+// program halts. Each is a value of register 0 that would halt the program at that comparison.
+fn candidates(mut m: Machine<u64>) -> Result<Vec<u64>> {
+    let (compare, compared) = comparison(&m.instructions)?;
+    let loops = DivisionLoop::find(m.bindip, &m.instructions);
+    let mut seen = HashSet::new();
+    let mut values = Vec::new();
+    loop {
+        if m.ip() == compare {
+            let value = match compared {
+                Compared::Register(r) => m.registers[r],
+                Compared::Value(value) => value,
+            };
+            if !seen.insert(value) {
+                break;
+            }
+            values.push(value);
+        }
+        if step(&mut m, &loops).is_none() {
+            break;
+        }
     }
+    Ok(values)
 }
 
-struct Machine {
-    registers: [u64; 6],
-    bindip: usize,
-    instructions: Vec<Instruction>,
+#[derive(Debug, PartialEq)]
+struct Solution {
+    first: u64,
+    last: u64,
+    candidates: usize,
 }
 
-fn bool_to_u64(b: bool) -> u64 {
-    if b {
-        1
-    } else {
-        0
+// The values of register 0 that halt the program soonest and latest. The program only halts once
+// its one test of register 0 succeeds, so run it with a register 0 nothing matches: the first
+// candidate halts soonest, and the last new one before they start repeating halts latest.
+fn solve(m: Machine<u64>) -> Result<Solution> {
+    let values = candidates(m)?;
+    match (values.first(), values.last()) {
+        (Some(&first), Some(&last)) => Ok(Solution {
+            first,
+            last,
+            candidates: values.len(),
+        }),
+        _ => bail!("halted before comparing against register 0"),
     }
 }
 
-impl Machine {
-    fn new(bindip: usize, instructions: Vec<Instruction>) -> Machine {
-        Machine {
-            registers: [0; 6],
-            bindip,
-            instructions,
-        }
+#[test]
+fn test_division_loop() -> Result<()> {
+    // The input's loop with other registers, dividing r1 by 10 into r4.
+    let program = |n: u64, t: u64| {
+        format!(
+            "#ip 3\nseti {} 0 1\nseti {} 0 4\naddi 4 1 2\nmuli 2 10 2\ngtrr 2 1 2\naddr 3 2 3\n\
+             addi 3 1 3\nseti 9 0 3\naddi 4 1 4\nseti 1 0 3\nsetr 4 0 5\n",
+            n, t
+        )
+    };
+    let m = Machine::parse(program(0, 0).as_bytes())?;
+    let loops = DivisionLoop::find(m.bindip, &m.instructions);
+    assert_eq!(
+        loops,
+        vec![DivisionLoop {
+            start: 2,
+            exit: 10,
+            t: 4,
+            u: 2,
+            n: 1,
+            k: 10
+        }]
+    );
+
+    for &(n, t) in &[(0, 0), (9, 0), (10, 0), (1234, 0), (1234, 5), (1234, 200)] {
+        let mut fast = Machine::parse(program(n, t).as_bytes())?;
+        let mut slow = Machine::parse(program(n, t).as_bytes())?;
+        while step(&mut fast, &loops).is_some() {}
+        while slow.step().is_some() {}
+        assert_eq!(fast.registers, slow.registers, "{} / 10 from {}", n, t);
     }
+    Ok(())
+}
 
-    // fn reset(&mut self) {
-    //     self.registers = [0; 6];
-    // }
+#[test]
+fn test_solve() -> Result<()> {
+    // Squares register 1 plus one, mod 16, until it equals register 0: 1, 2, 5, 10, 5, ...
+    let program = "#ip 5
+seti 0 0 1
+mulr 1 1 1
+addi 1 1 1
+bani 1 15 1
+eqrr 1 0 2
+addr 2 5 5
+seti 0 0 5
+";
+    assert_eq!(
+        candidates(Machine::parse(program.as_bytes())?)?,
+        [1, 2, 5, 10]
+    );
+    let solution = solve(Machine::parse(program.as_bytes())?)?;
+    assert_eq!(
+        solution,
+        Solution {
+            first: 1,
+            last: 10,
+            candidates: 4
+        }
+    );
 
-    fn reg(&self, r: u64) -> u64 {
-        self.registers[r as usize]
-    }
+    let err = solve(Machine::parse(&b"#ip 1\nseti 3 0 2\n"[..])?).unwrap_err();
+    assert_eq!(err.to_string(), "no comparison against register 0");
+    let err = solve(Machine::parse(&b"#ip 3\nseti 4 0 3\neqrr 1 0 2\n"[..])?).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "halted before comparing against register 0"
+    );
+    Ok(())
+}
 
-    fn ip(&self) -> usize {
-        self.registers[self.bindip] as usize
-    }
+#[test]
+fn test_comparison() -> Result<()> {
+    // The same sequence as `test_solve`, kept in register 3 with register 0 on the left and the
+    // instruction pointer bound to register 1.
+    let program = "#ip 1
+seti 0 0 3
+mulr 3 3 3
+addi 3 1 3
+bani 3 15 3
+eqrr 0 3 4
+addr 4 1 1
+seti 0 0 1
+";
+    let m = Machine::parse(program.as_bytes())?;
+    assert_eq!(comparison(&m.instructions)?, (4, Compared::Register(3)));
+    assert_eq!(
+        solve(m)?,
+        Solution {
+            first: 1,
+            last: 10,
+            candidates: 4
+        }
+    );
 
-    fn execute(&mut self) {
-        use OpCode::*;
-        let Instruction {
-            opcode,
-            in1: a,
-            in2: b,
-            out,
-        } = self.instructions[self.ip()];
-        self.registers[out as usize] = match opcode {
-            addr => self.reg(a) + self.reg(b),
-            addi => self.reg(a) + b,
-            mulr => self.reg(a) * self.reg(b),
-            muli => self.reg(a) * b,
-            banr => self.reg(a) & self.reg(b),
-            bani => self.reg(a) & b,
-            borr => self.reg(a) | self.reg(b),
-            bori => self.reg(a) | b,
-            setr => self.reg(a),
-            seti => a,
-            gtir => bool_to_u64(a > self.reg(b)),
-            gtri => bool_to_u64(self.reg(a) > b),
-            gtrr => bool_to_u64(self.reg(a) > self.reg(b)),
-            eqir => bool_to_u64(a == self.reg(b)),
-            eqri => bool_to_u64(self.reg(a) == b),
-            eqrr => bool_to_u64(self.reg(a) == self.reg(b)),
-        };
-    }
+    let m = Machine::<u64>::parse(&b"#ip 5\neqri 0 7 1\neqir 7 2 1\n"[..])?;
+    assert_eq!(comparison(&m.instructions)?, (0, Compared::Value(7)));
+    let m = Machine::<u64>::parse(&b"#ip 5\neqri 0 7 1\neqrr 1 0 1\n"[..])?;
+    let err = comparison(&m.instructions).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "several comparisons against register 0, at 0 1"
+    );
+    Ok(())
+}
 
-    fn step(&mut self) -> Option<()> {
-        self.execute();
-        self.registers[self.bindip] += 1;
-        if self.ip() < self.instructions.len() {
-            Some(())
-        } else {
-            None
+#[test]
+fn test_input() -> Result<()> {
+    let solution = solve(Machine::parse(&include_bytes!("../input")[..])?)?;
+    assert_eq!(
+        solution,
+        Solution {
+            first: 11285115,
+            last: 2947113,
+            candidates: 10037
         }
-    }
+    );
+    Ok(())
 }
 
 fn main() -> Result<()> {
-    let stdin = io::stdin();
-    let mut lines = stdin.lock().lines();
-    let bindip = lines.next().expect("empty input")?;
-    ensure!(bindip.starts_with("#ip "), "#ip");
-    let bindip = bindip[4..].parse()?;
-    let instructions: Vec<Instruction> = lines
-        .map(|l| l.unwrap().parse())
-        .collect::<Result<Vec<Instruction>>>()?;
-
-    let mut m = Machine::new(bindip, instructions);
-    m.registers[0] = 11285115;
-    println!("#ip {}", bindip);
-    let mut exit = false;
-    while !exit {
-        // let prefix = format!("ip={} {:?} {}", m.ip(), m.registers, m.instructions[m.ip()]);
-        exit = m.step().is_none();
-        // println!("{} {:?}", prefix, m.registers);
-    }
-
-    let mut candidates = HashSet::new();
-    let mut r = [0u64; 6];
-    'i6: loop {
-        // 06 bori 1 65536 3
-        r[3] = r[1] | 65536;
-        // 07 seti 10905776 4 1
-        r[1] = 10905776;
-        'i8: loop {
-            // 08 bani 3 255 4
-            r[4] = r[3] & 255;
-            // 09 addr 1 4 1
-            r[1] += r[4];
-            // 10 bani 1 16777215 1
-            r[1] &= 16777215;
-            // 11 muli 1 65899 1
-            r[1] *= 65899;
-            // 12 bani 1 16777215 1
-            r[1] &= 16777215;
-            // 13 gtir 256 3 4
-            // 14 addr 4 2 2
-            // 16 seti 27 1 2
-            if 256 > r[3] {
-                if !candidates.insert(r[1]) {
-                    break 'i6;
-                }
-                println!("{}", r[1]);
-                // 28 eqrr 1 0 4
-                // 29 addr 4 2 2
-                if r[1] == r[0] {
-                    break 'i6;
-                }
-                // 30 seti 5 1 2
-                continue 'i6;
-            }
-
-            // // 15 addi 2 1 2
-            // // 17 seti 0 6 4
-            // r[4] = 0;
-            // loop {
-            //     // 18 addi 4 1 5
-            //     r[5] = r[4] + 1;
-            //     // 19 muli 5 256 5
-            //     r[5] *= 256;
-            //     // 20 gtrr 5 3 5
-            //     // 21 addr 5 2 2
-            //     // 23 seti 25 1 2
-            //     if r[5] > r[3] {
-            //         // 26 setr 4 7 3
-            //         r[3] = r[4];
-            //         // 27 seti 7 4 2
-            //         continue 'i8;
-            //     }
-            //     // 22 addi 2 1 2
-            //     // 24 addi 4 1 4
-            //     r[4] += 1;
-            //     // 25 seti 17 9 2
-            // }
-            r[3] = r[3] / 256;
+    let mut stats = false;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--stats" => stats = true,
+            _ => bail!("unexpected argument {}", arg),
         }
     }
 
+    let stdin = io::stdin();
+    let solution = solve(Machine::parse(stdin.lock())?)?;
+    println!("{}", solution.first);
+    println!("{}", solution.last);
+    if stats {
+        eprintln!(
+            "{} distinct candidates before they repeat",
+            solution.candidates
+        );
+    }
     Ok(())
 }
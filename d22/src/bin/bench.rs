@@ -0,0 +1,43 @@
+use d22::{Cave, Coord, MARGIN};
+use std::time::{Duration, Instant};
+
+const CAVES: usize = 5;
+// The input's depth. Arbitrary depths can make caves whose quickest way is far too long to find.
+const DEPTH: u64 = 11109;
+
+// Targets shaped like the puzzle's, narrow and deep, from a fixed linear congruential sequence so
+// every run sees the same caves.
+fn targets() -> Vec<Coord> {
+    let mut seed: u32 = 2018;
+    let mut roll = |n: u64| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        u64::from(seed >> 16) % n
+    };
+    (0..CAVES)
+        .map(|_| Coord(roll(50), 500 + roll(400)))
+        .collect()
+}
+
+// Times building the cave and running both parts on it.
+fn time(build: impl Fn() -> Cave, target: Coord) -> (Duration, (u64, u64)) {
+    let start = Instant::now();
+    let cave = build();
+    let answers = (cave.risk_level(Coord(0, 0), target), cave.astar());
+    (start.elapsed(), answers)
+}
+
+fn main() {
+    let (mut cached_total, mut grid_total) = (Duration::default(), Duration::default());
+    for target in targets() {
+        let (cached, cached_answers) = time(|| Cave::new(DEPTH, target), target);
+        let (grid, grid_answers) = time(|| Cave::with_grid(DEPTH, target, MARGIN), target);
+        assert_eq!(cached_answers, grid_answers);
+        println!(
+            "target {},{}: new {:?}, with_grid {:?} ({} / {})",
+            target.0, target.1, cached, grid, grid_answers.0, grid_answers.1
+        );
+        cached_total += cached;
+        grid_total += grid;
+    }
+    println!("total: new {:?}, with_grid {:?}", cached_total, grid_total);
+}
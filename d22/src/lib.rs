@@ -0,0 +1,332 @@
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::error::Error;
+use std::result;
+
+pub type Result<T> = result::Result<T, Box<dyn Error>>;
+
+#[derive(Clone, Copy)]
+enum RegionType {
+    Rocky,
+    Narrow,
+    Wet,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Coord(pub u64, pub u64);
+
+impl Coord {
+    fn manhattan_distance(&self, other: Coord) -> u64 {
+        ((self.0 as i64 - other.0 as i64).abs() + (self.1 as i64 - other.1 as i64).abs()) as u64
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Tool {
+    Torch,
+    Gear,
+    Neither,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct State(Reverse<u64>, Tool, Coord);
+
+// Moves to try, by the least time they could reach the target in, with the state each is from.
+type Frontier = BinaryHeap<(Reverse<u64>, State, (Coord, Tool))>;
+
+// How far past the target `main` precomputes erosion levels. The quickest way to the target
+// rarely strays further than this, and anything beyond still works through the cache.
+pub const MARGIN: u64 = 100;
+
+pub struct Cave {
+    target: Coord,
+    depth: u64,
+    // Erosion levels of every region up to `grid_size`, row by row.
+    grid: Vec<u64>,
+    grid_size: Coord,
+    erosion_cache: RefCell<HashMap<Coord, u64>>,
+}
+
+impl Cave {
+    pub fn new(depth: u64, target: Coord) -> Cave {
+        Cave {
+            target,
+            depth,
+            grid: Vec::new(),
+            grid_size: Coord(0, 0),
+            erosion_cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // Precomputes erosion levels up to `margin` past the target in each direction, so they're
+    // looked up without hashing. `src/bin/bench.rs` compares it against `new`.
+    pub fn with_grid(depth: u64, target: Coord, margin: u64) -> Cave {
+        let mut cave = Cave::new(depth, target);
+        let Coord(width, height) = Coord(target.0 + margin + 1, target.1 + margin + 1);
+        let mut grid = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let index = if Coord(x, y) == target {
+                    0
+                } else if y == 0 {
+                    x * 16807
+                } else if x == 0 {
+                    y * 48271
+                } else {
+                    let i = (x + y * width) as usize;
+                    grid[i - 1] * grid[i - width as usize]
+                };
+                grid.push((index + depth) % 20183);
+            }
+        }
+        cave.grid = grid;
+        cave.grid_size = Coord(width, height);
+        cave
+    }
+
+    fn geologic_index(&self, coord: Coord) -> u64 {
+        let Coord(x, y) = coord;
+        if coord == Coord(0, 0) || coord == self.target {
+            0
+        } else if y == 0 {
+            x * 16807
+        } else if x == 0 {
+            y * 48271
+        } else {
+            self.erosion_level(Coord(x - 1, y)) * self.erosion_level(Coord(x, y - 1))
+        }
+    }
+
+    fn erosion_level(&self, coord: Coord) -> u64 {
+        let (Coord(x, y), Coord(width, height)) = (coord, self.grid_size);
+        if x < width && y < height {
+            return self.grid[(x + y * width) as usize];
+        }
+        if let Some(&level) = self.erosion_cache.borrow().get(&coord) {
+            return level;
+        }
+        let level = (self.geologic_index(coord) + self.depth) % 20183;
+        self.erosion_cache.borrow_mut().insert(coord, level);
+        level
+    }
+
+    fn region_type(&self, coord: Coord) -> RegionType {
+        match self.erosion_level(coord) % 3 {
+            0 => RegionType::Rocky,
+            1 => RegionType::Wet,
+            2 => RegionType::Narrow,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn risk_level(&self, tl: Coord, br: Coord) -> u64 {
+        let mut sum = 0;
+        for x in tl.0..=br.0 {
+            for y in tl.1..=br.1 {
+                sum += match self.region_type(Coord(x, y)) {
+                    RegionType::Rocky => 0,
+                    RegionType::Wet => 1,
+                    RegionType::Narrow => 2,
+                };
+            }
+        }
+        sum
+    }
+
+    fn region_tools(&self, coord: Coord) -> &[Tool; 2] {
+        match self.region_type(coord) {
+            RegionType::Rocky => &[Tool::Gear, Tool::Torch],
+            RegionType::Wet => &[Tool::Gear, Tool::Neither],
+            RegionType::Narrow => &[Tool::Torch, Tool::Neither],
+        }
+    }
+
+    // Queues a move one step into `state`'s region, if its tool can be used there, remembering
+    // the state it came from.
+    fn explore(&self, state: State, from: (Coord, Tool), frontier: &mut Frontier) {
+        let State(Reverse(distance), tool, coord) = state;
+        if self.region_tools(coord).contains(&tool) {
+            frontier.push((
+                Reverse(distance + 1 + coord.manhattan_distance(self.target)),
+                State(Reverse(distance + 1), tool, coord),
+                from,
+            ));
+        }
+    }
+
+    pub fn astar(&self) -> u64 {
+        self.astar_path().0
+    }
+
+    // The fewest minutes to reach the target holding the torch, and the position and tool after
+    // each move or switch along a quickest way there, from the mouth of the cave.
+    pub fn astar_path(&self) -> (u64, Vec<(Coord, Tool)>) {
+        let start = (Coord(0, 0), Tool::Torch);
+        let mut came_from = HashMap::new();
+        let mut frontier = BinaryHeap::new();
+        frontier.push((
+            Reverse(Coord(0, 0).manhattan_distance(self.target)),
+            State(Reverse(0), Tool::Torch, Coord(0, 0)),
+            start,
+        ));
+        while let Some((_, state, from)) = frontier.pop() {
+            let State(Reverse(distance), tool, Coord(x, y)) = state;
+            if came_from.contains_key(&(Coord(x, y), tool)) {
+                continue;
+            }
+            came_from.insert((Coord(x, y), tool), from);
+            if Coord(x, y) == self.target && tool == Tool::Torch {
+                let mut path = vec![(Coord(x, y), tool)];
+                while path[path.len() - 1] != start {
+                    path.push(came_from[&path[path.len() - 1]]);
+                }
+                path.reverse();
+                return (distance, path);
+            }
+            let here = (Coord(x, y), tool);
+            self.explore(
+                State(Reverse(distance), tool, Coord(x + 1, y)),
+                here,
+                &mut frontier,
+            );
+            self.explore(
+                State(Reverse(distance), tool, Coord(x, y + 1)),
+                here,
+                &mut frontier,
+            );
+            if x > 0 {
+                self.explore(
+                    State(Reverse(distance), tool, Coord(x - 1, y)),
+                    here,
+                    &mut frontier,
+                );
+            }
+            if y > 0 {
+                self.explore(
+                    State(Reverse(distance), tool, Coord(x, y - 1)),
+                    here,
+                    &mut frontier,
+                );
+            }
+            for &switch_tool in self.region_tools(Coord(x, y)) {
+                frontier.push((
+                    Reverse(distance + 7 + Coord(x, y).manhattan_distance(self.target)),
+                    State(Reverse(distance + 7), switch_tool, Coord(x, y)),
+                    here,
+                ));
+            }
+        }
+        unreachable!();
+    }
+}
+
+// Reads the puzzle's `depth: N` and `target: X,Y` lines.
+pub fn parse_input(s: &str) -> Result<(u64, Coord)> {
+    let mut lines = s.lines().map(str::trim).filter(|l| !l.is_empty());
+    let mut field = |name: &str| -> Result<&str> {
+        let line = lines
+            .next()
+            .ok_or_else(|| format!("missing `{}: ...` line", name))?;
+        let value = line.strip_prefix(name).and_then(|l| l.strip_prefix(':'));
+        Ok(value
+            .ok_or_else(|| format!("expected `{}: ...`, got `{}`", name, line))?
+            .trim())
+    };
+    let depth = field("depth")?;
+    let depth = depth
+        .parse()
+        .map_err(|_| format!("invalid depth `{}`", depth))?;
+    let target = field("target")?;
+    let coord = match target.split_once(',') {
+        Some((x, y)) => x
+            .trim()
+            .parse()
+            .and_then(|x| Ok(Coord(x, y.trim().parse()?)))
+            .ok(),
+        None => None,
+    };
+    let target = coord.ok_or_else(|| format!("invalid target `{}`, expected `X,Y`", target))?;
+    if let Some(line) = lines.next() {
+        return Err(format!("unexpected line `{}`", line).into());
+    }
+    Ok((depth, target))
+}
+
+#[test]
+fn test_parse_input() -> Result<()> {
+    assert_eq!(
+        parse_input("depth: 510\ntarget: 10,10\n")?,
+        (510, Coord(10, 10))
+    );
+    assert_eq!(
+        parse_input("  depth:11109\n\ntarget: 9, 731")?,
+        (11109, Coord(9, 731))
+    );
+    let err = |s: &str| parse_input(s).unwrap_err().to_string();
+    assert_eq!(err(""), "missing `depth: ...` line");
+    assert_eq!(err("depth: 510\n"), "missing `target: ...` line");
+    assert_eq!(
+        err("target: 10,10\ndepth: 510\n"),
+        "expected `depth: ...`, got `target: 10,10`"
+    );
+    assert_eq!(err("depth: -1\ntarget: 10,10\n"), "invalid depth `-1`");
+    assert_eq!(
+        err("depth: 510\ntarget: 10\n"),
+        "invalid target `10`, expected `X,Y`"
+    );
+    assert_eq!(err("depth: 510\ntarget: 10,10\n1\n"), "unexpected line `1`");
+    Ok(())
+}
+
+#[test]
+fn test_sample() -> Result<()> {
+    let (depth, target) = parse_input("depth: 510\ntarget: 10,10\n")?;
+    let cave = Cave::new(depth, target);
+    assert_eq!(cave.risk_level(Coord(0, 0), cave.target), 114);
+    assert_eq!(cave.astar(), 45);
+    Ok(())
+}
+
+#[test]
+fn test_astar_path() -> Result<()> {
+    let cave = Cave::new(510, Coord(10, 10));
+    let (minutes, path) = cave.astar_path();
+    assert_eq!(minutes, 45);
+    assert_eq!(path.first(), Some(&(Coord(0, 0), Tool::Torch)));
+    assert_eq!(path.last(), Some(&(Coord(10, 10), Tool::Torch)));
+    // Each step either moves one region keeping the tool, for a minute, or switches tool in
+    // place, for seven.
+    let mut total = 0;
+    for step in path.windows(2) {
+        let ((from, from_tool), (to, to_tool)) = (step[0], step[1]);
+        total += match (from.manhattan_distance(to), from_tool == to_tool) {
+            (1, true) => 1,
+            (0, false) => 7,
+            _ => panic!("not a single step: {:?}", step),
+        };
+        assert!(cave.region_tools(to).contains(&to_tool));
+    }
+    assert_eq!(total, 45);
+    Ok(())
+}
+
+#[test]
+fn test_with_grid() -> Result<()> {
+    let cached = Cave::new(510, Coord(10, 10));
+    // A margin small enough that the quickest way to the target leaves the grid.
+    for &margin in &[0, 2, MARGIN] {
+        let grid = Cave::with_grid(510, Coord(10, 10), margin);
+        for y in 0..30 {
+            for x in 0..30 {
+                assert_eq!(
+                    grid.erosion_level(Coord(x, y)),
+                    cached.erosion_level(Coord(x, y))
+                );
+            }
+        }
+        assert_eq!(grid.risk_level(Coord(0, 0), grid.target), 114);
+        assert_eq!(grid.astar_path(), cached.astar_path());
+    }
+    Ok(())
+}
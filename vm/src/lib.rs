@@ -0,0 +1,431 @@
+//! The register machine shared by days 16, 19 and 21.
+
+use std::error;
+use std::fmt;
+use std::io::{self, BufRead};
+use std::ops::{BitAnd, BitOr};
+use std::result;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl error::Error for Error {}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// A register width. Register numbers are stored as words too, and index the register file.
+pub trait Word:
+    Copy
+    + PartialEq
+    + PartialOrd
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + FromStr
+    + fmt::Display
+{
+    fn from_bool(b: bool) -> Self;
+    fn index(self) -> usize;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_word {
+    ($($t:ty),*) => {
+        $(
+            impl Word for $t {
+                fn from_bool(b: bool) -> Self {
+                    b as $t
+                }
+
+                fn index(self) -> usize {
+                    self as usize
+                }
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$t>::wrapping_add(self, other)
+                }
+
+                fn wrapping_mul(self, other: Self) -> Self {
+                    <$t>::wrapping_mul(self, other)
+                }
+
+                fn checked_add(self, other: Self) -> Option<Self> {
+                    <$t>::checked_add(self, other)
+                }
+
+                fn checked_mul(self, other: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, other)
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(u32, u64, usize);
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpCode {
+    addr,
+    addi,
+    mulr,
+    muli,
+    banr,
+    bani,
+    borr,
+    bori,
+    setr,
+    seti,
+    gtir,
+    gtri,
+    gtrr,
+    eqir,
+    eqri,
+    eqrr,
+}
+
+impl OpCode {
+    pub fn variants() -> impl Iterator<Item = OpCode> {
+        use OpCode::*;
+        static OPCODES: [OpCode; 16] = [
+            addr, addi, mulr, muli, banr, bani, borr, bori, setr, seti, gtir, gtri, gtrr, eqir,
+            eqri, eqrr,
+        ];
+        OPCODES.iter().cloned()
+    }
+}
+
+impl OpCode {
+    /// Whether the first and second inputs name registers rather than being immediate values.
+    pub fn register_inputs(self) -> (bool, bool) {
+        use OpCode::*;
+        match self {
+            addr | mulr | banr | borr | gtrr | eqrr => (true, true),
+            addi | muli | bani | bori | setr | gtri | eqri => (true, false),
+            gtir | eqir => (false, true),
+            seti => (false, false),
+        }
+    }
+}
+
+impl FromStr for OpCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        OpCode::variants()
+            .find(|opcode| opcode.to_string() == s)
+            .ok_or_else(|| Error(format!("unknown opcode `{}`", s)))
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+pub struct Instruction<W> {
+    pub opcode: OpCode,
+    pub in1: W,
+    pub in2: W,
+    pub out: W,
+}
+
+impl<W: Word> fmt::Display for Instruction<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.opcode, self.in1, self.in2, self.out)
+    }
+}
+
+impl<W: Word> FromStr for Instruction<W> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let words: Vec<_> = s.split_whitespace().collect();
+        let (opcode, operands) = match words.as_slice() {
+            [opcode, in1, in2, out] => (opcode, [in1, in2, out]),
+            _ => return Err(Error(format!("expected 4 words, got `{}`", s))),
+        };
+        let mut operands = operands.iter().map(|word| {
+            word.parse()
+                .map_err(|_| Error(format!("invalid operand `{}` in `{}`", word, s)))
+        });
+        Ok(Instruction {
+            opcode: opcode.parse()?,
+            in1: operands.next().unwrap()?,
+            in2: operands.next().unwrap()?,
+            out: operands.next().unwrap()?,
+        })
+    }
+}
+
+// Computes the value `instruction` writes, or None if `checked` and the arithmetic overflows.
+fn evaluate<W: Word>(registers: &[W], instruction: &Instruction<W>, checked: bool) -> Option<W> {
+    use OpCode::*;
+    let &Instruction {
+        opcode,
+        in1: a,
+        in2: b,
+        ..
+    } = instruction;
+    let reg = |r: W| registers[r.index()];
+    let add = |x: W, y: W| {
+        if checked {
+            x.checked_add(y)
+        } else {
+            Some(x.wrapping_add(y))
+        }
+    };
+    let mul = |x: W, y: W| {
+        if checked {
+            x.checked_mul(y)
+        } else {
+            Some(x.wrapping_mul(y))
+        }
+    };
+    Some(match opcode {
+        addr => add(reg(a), reg(b))?,
+        addi => add(reg(a), b)?,
+        mulr => mul(reg(a), reg(b))?,
+        muli => mul(reg(a), b)?,
+        banr => reg(a) & reg(b),
+        bani => reg(a) & b,
+        borr => reg(a) | reg(b),
+        bori => reg(a) | b,
+        setr => reg(a),
+        seti => a,
+        gtir => W::from_bool(a > reg(b)),
+        gtri => W::from_bool(reg(a) > b),
+        gtrr => W::from_bool(reg(a) > reg(b)),
+        eqir => W::from_bool(a == reg(b)),
+        eqri => W::from_bool(reg(a) == b),
+        eqrr => W::from_bool(reg(a) == reg(b)),
+    })
+}
+
+/// Executes `instruction` against `registers`, which may be any number of registers of any width.
+/// Arithmetic wraps on overflow.
+pub fn execute<W: Word>(registers: &mut [W], instruction: &Instruction<W>) {
+    registers[instruction.out.index()] = evaluate(registers, instruction, false).unwrap();
+}
+
+// Checks that every register `instruction` reads or writes exists.
+fn validate<W: Word>(registers: &[W], instruction: &Instruction<W>) -> Result<()> {
+    let (in1, in2) = instruction.opcode.register_inputs();
+    let used = [
+        (in1, instruction.in1),
+        (in2, instruction.in2),
+        (true, instruction.out),
+    ];
+    for &(is_register, r) in &used {
+        if is_register && r.index() >= registers.len() {
+            return Err(Error(format!(
+                "register {} out of range for {} registers in `{}`",
+                r,
+                registers.len(),
+                instruction
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Like `execute`, but fails without touching the registers if `instruction` names a register
+/// that doesn't exist.
+pub fn try_execute<W: Word>(registers: &mut [W], instruction: &Instruction<W>) -> Result<()> {
+    validate(registers, instruction)?;
+    execute(registers, instruction);
+    Ok(())
+}
+
+/// Like `try_execute`, but also fails without touching the registers if the arithmetic overflows.
+pub fn checked_execute<W: Word>(registers: &mut [W], instruction: &Instruction<W>) -> Result<()> {
+    validate(registers, instruction)?;
+    let value = evaluate(registers, instruction, true)
+        .ok_or_else(|| Error(format!("arithmetic overflow in `{}`", instruction)))?;
+    registers[instruction.out.index()] = value;
+    Ok(())
+}
+
+/// The operands of `instruction` as register numbers, if it has the given opcode. For matching
+/// known loops in a program.
+pub fn operands<W: Word>(
+    instruction: &Instruction<W>,
+    opcode: OpCode,
+) -> Option<(usize, usize, usize)> {
+    if instruction.opcode == opcode {
+        Some((
+            instruction.in1.index(),
+            instruction.in2.index(),
+            instruction.out.index(),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Given one input of a commutative instruction, the other.
+pub fn other(in1: usize, in2: usize, known: usize) -> Option<usize> {
+    if in1 == known {
+        Some(in2)
+    } else if in2 == known {
+        Some(in1)
+    } else {
+        None
+    }
+}
+
+/// A program with its instruction pointer bound to one of six registers, as on days 19 and 21.
+pub struct Machine<W> {
+    pub registers: [W; 6],
+    pub bindip: usize,
+    pub instructions: Vec<Instruction<W>>,
+}
+
+impl<W: Word> Machine<W> {
+    pub fn new(bindip: usize, instructions: Vec<Instruction<W>>) -> Machine<W> {
+        Machine {
+            registers: [W::from_bool(false); 6],
+            bindip,
+            instructions,
+        }
+    }
+
+    /// Reads an `#ip N` line binding the instruction pointer, then one instruction per line.
+    pub fn parse(r: impl BufRead) -> Result<Machine<W>> {
+        let mut lines = r.lines();
+        let read = |line: Option<io::Result<String>>| match line {
+            Some(line) => line.map_err(|e| Error(e.to_string())),
+            None => Err(Error("empty input".to_string())),
+        };
+        let header = read(lines.next())?;
+        let bindip = header
+            .strip_prefix("#ip ")
+            .and_then(|r| r.trim().parse().ok())
+            .filter(|&r| r < 6)
+            .ok_or_else(|| Error(format!("expected `#ip N` with N below 6, got `{}`", header)))?;
+        let mut instructions = Vec::new();
+        for (i, line) in lines.enumerate() {
+            let line = read(Some(line))?;
+            let instruction = line
+                .parse()
+                .map_err(|e| Error(format!("line {}: {}", i + 2, e)))?;
+            instructions.push(instruction);
+        }
+        Ok(Machine::new(bindip, instructions))
+    }
+
+    pub fn ip(&self) -> usize {
+        self.registers[self.bindip].index()
+    }
+
+    /// Executes the instruction at the instruction pointer and moves past it, or returns None
+    /// once that leaves the program. Returns None without executing anything if the instruction
+    /// pointer is already outside it.
+    pub fn step(&mut self) -> Option<()> {
+        let instruction = self.instructions.get(self.ip())?;
+        execute(&mut self.registers, instruction);
+        self.advance()
+    }
+
+    /// Moves the instruction pointer past its instruction without executing it, as after a step.
+    pub fn advance(&mut self) -> Option<()> {
+        let ip = &mut self.registers[self.bindip];
+        *ip = ip.wrapping_add(W::from_bool(true));
+        if self.ip() < self.instructions.len() {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn test_machine() -> Result<()> {
+    // The sample from day 19.
+    let program = "#ip 0\nseti 5 0 1\nseti 6 0 2\naddi 0 1 0\naddr 1 2 3\nsetr 1 0 0\n\
+                   seti 8 0 4\nseti 9 0 5\n";
+    let mut m = Machine::<u64>::parse(program.as_bytes())?;
+    while let Some(()) = m.step() {}
+    assert_eq!(m.registers, [7, 5, 6, 0, 0, 9]);
+
+    let mut m = Machine::<u64>::parse(program.as_bytes())?;
+    m.advance();
+    m.step();
+    assert_eq!((m.ip(), m.registers), (2, [2, 0, 6, 0, 0, 0]));
+
+    // An instruction pointer outside the program halts without executing anything.
+    let mut m = Machine::<u64>::parse(&b"#ip 0\n"[..])?;
+    assert_eq!(m.step(), None);
+    let mut m = Machine::<u64>::parse(program.as_bytes())?;
+    m.registers = [9, 1, 0, 0, 0, 0];
+    assert_eq!(m.step(), None);
+    assert_eq!(m.registers, [9, 1, 0, 0, 0, 0]);
+
+    let err = |program: &str| {
+        Machine::<u64>::parse(program.as_bytes())
+            .err()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(err(""), "empty input");
+    assert_eq!(
+        err("#ip 6\n"),
+        "expected `#ip N` with N below 6, got `#ip 6`"
+    );
+    assert_eq!(
+        err("#ip 1\nseti 5 0 1\nseti 5\n"),
+        "line 3: expected 4 words, got `seti 5`"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_execute() {
+    // The sample from day 16, which behaves like mulr, addi and seti.
+    let matching: Vec<_> = OpCode::variants()
+        .filter(|&opcode| {
+            let mut registers = [3u32, 2, 1, 1];
+            execute(
+                &mut registers,
+                &Instruction {
+                    opcode,
+                    in1: 2,
+                    in2: 1,
+                    out: 2,
+                },
+            );
+            registers == [3, 2, 2, 1]
+        })
+        .collect();
+    assert_eq!(matching, [OpCode::addi, OpCode::mulr, OpCode::seti]);
+
+    let mut registers = [0u64; 6];
+    registers[1] = 7;
+    execute(&mut registers, &"gtri 1 5 4".parse().unwrap());
+    execute(&mut registers, &"muli 1 3 5".parse().unwrap());
+    assert_eq!(registers, [0, 7, 0, 0, 1, 21]);
+}
+
+#[test]
+fn test_parse() -> Result<()> {
+    let instruction: Instruction<u64> = "eqrr 1 0 4".parse()?;
+    assert_eq!(instruction.opcode, OpCode::eqrr);
+    assert_eq!(instruction.to_string(), "eqrr 1 0 4");
+    for opcode in OpCode::variants() {
+        assert_eq!(opcode.to_string().parse::<OpCode>()?, opcode);
+    }
+    assert!("addx 1 2 3".parse::<Instruction<u64>>().is_err());
+    assert!("addi 1 2".parse::<Instruction<u64>>().is_err());
+    assert!("addi 1 -2 3".parse::<Instruction<u64>>().is_err());
+    Ok(())
+}